@@ -506,8 +506,12 @@ var Uprooted = (() => {
         return;
       }
       const theme = themes_default.find((t) => t.name === themeName);
-      if (theme && Object.keys(theme.variables).length > 0) {
-        setCssVariables(theme.variables);
+      const variables = theme?.variables ?? settings?.variables ?? {};
+      for (const name of Object.keys(variables)) {
+        allVarNames.add(name);
+      }
+      if (Object.keys(variables).length > 0) {
+        setCssVariables(variables);
       }
     },
     stop() {
@@ -26,7 +26,9 @@ trap 'echo ""; error "Script failed (line $LINENO). See error above."; echo "";
 
 INSTALL_DIR="$HOME/.local/share/uprooted"
 PROFILE_DIR="$HOME/.local/share/Root Communications/Root/profile/default"
+# Fallbacks only: the profiler.json manifest shipped with the artifacts takes precedence
 PROFILER_GUID="{D1A6F5A0-1234-4567-89AB-CDEF01234567}"
+PROFILER_FILE="libuprooted_profiler.so"
 VERSION="0.5.1"
 
 # Default channel: pre-release versions (dev/alpha/beta/rc) use canary channel
@@ -212,7 +214,8 @@ run_diagnose() {
     # 3. Check deployed files
     echo ""
     log "Checking deployed files..."
-    local files=("libuprooted_profiler.so" "UprootedHook.dll" "UprootedHook.deps.json" "uprooted-preload.js" "uprooted.css")
+    load_profiler_manifest
+    local files=("$PROFILER_FILE" "UprootedHook.dll" "UprootedHook.deps.json" "uprooted-preload.js" "uprooted.css")
     for f in "${files[@]}"; do
         if [[ -f "$INSTALL_DIR/$f" ]]; then
             log "  $f: exists"
@@ -350,7 +353,7 @@ run_uninstall() {
                 local tmp="${html}.tmp"
                 local inside_block=false
                 while IFS= read -r line; do
-                    if [[ "$line" == *"<!-- uprooted:start -->"* ]]; then
+                    if [[ "$line" == *"<!-- uprooted:start"* ]]; then
                         inside_block=true
                         continue
                     fi
@@ -800,16 +803,17 @@ download_prebuilt() {
 
     mkdir -p "$INSTALL_DIR"
     tar -xzf "$tarball" -C "$INSTALL_DIR"
-    chmod +x "$INSTALL_DIR/libuprooted_profiler.so"
     rm -rf "$tmpdir"
+    load_profiler_manifest
 
     # Verify all expected files exist
-    local files=("libuprooted_profiler.so" "UprootedHook.dll" "UprootedHook.deps.json" "uprooted-preload.js" "uprooted.css")
+    local files=("$PROFILER_FILE" "UprootedHook.dll" "UprootedHook.deps.json" "uprooted-preload.js" "uprooted.css")
     for f in "${files[@]}"; do
         if [[ ! -f "$INSTALL_DIR/$f" ]]; then
             die "Pre-built artifact missing after extraction: $f"
         fi
     done
+    chmod +x "$INSTALL_DIR/$PROFILER_FILE"
 
     log "Pre-built artifacts deployed to $INSTALL_DIR"
 }
@@ -825,7 +829,10 @@ deploy_local() {
     # Look for artifacts in repo build output
     local hook_out="$script_dir/hook/bin/Release/net10.0"
     local hook_out9="$script_dir/hook/bin/Release/net9.0"
-    local profiler="$script_dir/libuprooted_profiler.so"
+    # The manifest, when the profiler build wrote one, names the profiler file
+    local manifest="$script_dir/profiler.json"
+    load_profiler_manifest "$manifest"
+    local profiler="$script_dir/$PROFILER_FILE"
 
     # Validate required artifacts exist
     local missing=false
@@ -844,6 +851,9 @@ deploy_local() {
     mkdir -p "$INSTALL_DIR"
 
     cp "$profiler" "$INSTALL_DIR/"
+    if [[ -f "$manifest" ]]; then
+        cp "$manifest" "$INSTALL_DIR/profiler.json"
+    fi
     cp "$hook_out/UprootedHook.dll" "$INSTALL_DIR/"
     cp "$hook_out/UprootedHook.deps.json" "$INSTALL_DIR/"
     cp "$hook_out/nsfw-filter.js" "$INSTALL_DIR/" 2>/dev/null || true
@@ -856,7 +866,7 @@ deploy_local() {
     cp "$script_dir/dist/uprooted-preload.js" "$INSTALL_DIR/"
     cp "$script_dir/dist/uprooted.css" "$INSTALL_DIR/"
 
-    chmod +x "$INSTALL_DIR/libuprooted_profiler.so"
+    chmod +x "$INSTALL_DIR/$PROFILER_FILE"
 
     log "Local artifacts deployed to $INSTALL_DIR"
 }
@@ -871,6 +881,30 @@ deploy_artifacts() {
     fi
 }
 
+# ── Profiler GUID and file name from the manifest shipped with the artifacts ──
+# Reads $1 (default: the deployed $INSTALL_DIR/profiler.json). UPROOTED_PROFILER_MANIFEST
+# points at an alternate manifest (testing other profiler builds).
+
+load_profiler_manifest() {
+    local manifest="${UPROOTED_PROFILER_MANIFEST:-${1:-$INSTALL_DIR/profiler.json}}"
+    [[ -f "$manifest" ]] || return 0
+
+    local guid filename
+    guid=$(sed -n 's/.*"guid"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p' "$manifest" | head -n1)
+    filename=$(sed -n 's/.*"filename"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p' "$manifest" | head -n1)
+    if [[ "$guid" =~ ^\{[0-9A-Fa-f]{8}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{12}\}$ ]]; then
+        PROFILER_GUID="$guid"
+    else
+        warn "Ignoring invalid profiler GUID in $manifest: ${guid:-<missing>}"
+    fi
+    # A bare file name: it is joined onto $INSTALL_DIR and written into the wrapper
+    if [[ "$filename" =~ ^[A-Za-z0-9._-]+$ && "$filename" != .* ]]; then
+        PROFILER_FILE="$filename"
+    else
+        warn "Ignoring invalid profiler file name in $manifest: ${filename:-<missing>}"
+    fi
+}
+
 # ── Desktop environment detection ──
 
 is_kde() {
@@ -915,12 +949,12 @@ create_wrapper() {
         echo 'export DOTNET_EnableDiagnostics=1'
         echo 'export DOTNET_ENABLE_PROFILING=1'
         echo "export DOTNET_PROFILER='$PROFILER_GUID'"
-        echo "export DOTNET_PROFILER_PATH='$INSTALL_DIR/libuprooted_profiler.so'"
+        echo "export DOTNET_PROFILER_PATH='$INSTALL_DIR/$PROFILER_FILE'"
         echo 'export DOTNET_ReadyToRun=0'
         echo '# Legacy (.NET 8/9)'
         echo 'export CORECLR_ENABLE_PROFILING=1'
         echo "export CORECLR_PROFILER='$PROFILER_GUID'"
-        echo "export CORECLR_PROFILER_PATH='$INSTALL_DIR/libuprooted_profiler.so'"
+        echo "export CORECLR_PROFILER_PATH='$INSTALL_DIR/$PROFILER_FILE'"
         echo ''
         echo 'exec "$ROOT_EXEC" "$@"'
     } > "$wrapper"
@@ -1025,7 +1059,7 @@ strip_html_patches() {
             local tmp="${html}.tmp"
             local inside_block=false
             while IFS= read -r line; do
-                if [[ "$line" == *"<!-- uprooted:start -->"* ]]; then
+                if [[ "$line" == *"<!-- uprooted:start"* ]]; then
                     inside_block=true
                     continue
                 fi
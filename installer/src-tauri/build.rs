@@ -0,0 +1,41 @@
+//! Reads the profiler manifest staged alongside the embedded artifacts.
+//!
+//! `artifacts/profiler.json` (`{"guid": "{...}", "filename": "..."}`) is written by the
+//! build pipeline next to the profiler binary it stages. When it's absent (local builds)
+//! the long-standing GUID and per-platform filename are used.
+
+use std::path::Path;
+
+const DEFAULT_GUID: &str = "{D1A6F5A0-1234-4567-89AB-CDEF01234567}";
+
+fn main() {
+    let manifest_path = Path::new("artifacts/profiler.json");
+    println!("cargo:rerun-if-changed={}", manifest_path.display());
+
+    let default_filename = match std::env::var("CARGO_CFG_TARGET_OS").as_deref() {
+        Ok("windows") => "uprooted_profiler.dll",
+        Ok("macos") => "libuprooted_profiler.dylib",
+        _ => "libuprooted_profiler.so",
+    };
+
+    let manifest: serde_json::Value = match std::fs::read_to_string(manifest_path) {
+        Ok(content) => serde_json::from_str(&content)
+            .unwrap_or_else(|e| panic!("invalid {}: {}", manifest_path.display(), e)),
+        Err(_) => serde_json::Value::Null,
+    };
+
+    let guid = manifest["guid"].as_str().unwrap_or(DEFAULT_GUID);
+    let filename = manifest["filename"].as_str().unwrap_or(default_filename);
+
+    if !is_valid_guid(guid) {
+        panic!("profiler manifest guid {:?} is not a {{xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx}} GUID", guid);
+    }
+    if filename.is_empty() || filename.contains(['/', '\\']) {
+        panic!("profiler manifest filename {:?} must be a bare file name", filename);
+    }
+
+    println!("cargo:rustc-env=UPROOTED_PROFILER_GUID={}", guid);
+    println!("cargo:rustc-env=UPROOTED_PROFILER_FILENAME={}", filename);
+}
+
+include!("src/guid.rs");
@@ -0,0 +1,237 @@
+//! Snapshot history of target HTML files.
+//!
+//! Every time the patcher meets an unpatched target it stores the file's exact bytes as
+//! `backups/<target>/<unix time>-<sha256 prefix>.html` in the uprooted dir, skipping bytes
+//! already stored, and points `backups/<target>/original` at the snapshot. Uninstall uses
+//! that pointer to put back the exact pre-install bytes; older snapshots stay around (so a
+//! Root update doesn't lose the previous original) until pruned.
+
+use crate::fsutil::write_atomic;
+use crate::hash;
+use crate::hook::get_uprooted_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Length of the hash prefix in snapshot file names.
+const HASH_PREFIX_LEN: usize = 16;
+/// File in a target's directory naming its pre-install snapshot.
+const ORIGINAL_POINTER: &str = "original";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// Target path relative to the profile directory, `/`-separated.
+    pub target: String,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    /// SHA-256 prefix of the contents, hex.
+    pub hash: String,
+    pub path: PathBuf,
+}
+
+impl Snapshot {
+    /// `<target>/<created>-<hash>`, as accepted by `uprooted backups restore`.
+    pub fn id(&self) -> String {
+        format!("{}/{}", self.target, self.stem())
+    }
+
+    fn stem(&self) -> String {
+        format!("{}-{}", self.created, self.hash)
+    }
+
+    /// The snapshot's bytes, refusing ones that no longer match their hash.
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        let data = fs::read(&self.path)
+            .map_err(|e| format!("Failed to read snapshot {}: {}", self.path.display(), e))?;
+        if !hash::sha256_hex(&data).starts_with(&self.hash) {
+            return Err(format!("Snapshot {} is corrupt (hash mismatch)", self.id()));
+        }
+        Ok(data)
+    }
+}
+
+pub struct BackupStore {
+    root: PathBuf,
+}
+
+impl BackupStore {
+    /// The store in the uprooted dir.
+    pub fn open() -> Self {
+        Self::at(get_uprooted_dir().join("backups"))
+    }
+
+    fn at(root: PathBuf) -> Self {
+        BackupStore { root }
+    }
+
+    fn target_dir(&self, target: &str) -> PathBuf {
+        target.split('/').fold(self.root.clone(), |dir, part| dir.join(part))
+    }
+
+    /// Store `content` as a snapshot of `target`, or return the snapshot already holding it.
+    pub fn snapshot(&self, target: &str, content: &[u8]) -> Result<Snapshot, String> {
+        let hash = hash::sha256_hex(content)[..HASH_PREFIX_LEN].to_string();
+        if let Some(existing) = self.snapshots(target).into_iter().find(|s| s.hash == hash) {
+            return Ok(existing);
+        }
+
+        let dir = self.target_dir(target);
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = dir.join(format!("{}-{}.html", created, hash));
+        write_atomic(&path, content)?;
+        Ok(Snapshot { target: target.to_string(), created, hash, path })
+    }
+
+    /// Snapshots of `target`, newest first.
+    pub fn snapshots(&self, target: &str) -> Vec<Snapshot> {
+        let Ok(entries) = fs::read_dir(self.target_dir(target)) else {
+            return vec![];
+        };
+        let mut snapshots: Vec<Snapshot> = entries
+            .flatten()
+            .filter_map(|entry| parse_snapshot(target, entry.path()))
+            .collect();
+        snapshots.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| b.hash.cmp(&a.hash)));
+        snapshots
+    }
+
+    /// Targets with at least one snapshot, sorted.
+    pub fn targets(&self) -> Vec<String> {
+        let mut targets = Vec::new();
+        collect_targets(&self.root, &self.root, &mut targets);
+        targets.sort();
+        targets
+    }
+
+    /// Look up a snapshot by [`Snapshot::id`].
+    pub fn find(&self, id: &str) -> Option<Snapshot> {
+        let (target, _) = id.rsplit_once('/')?;
+        self.snapshots(target).into_iter().find(|s| s.id() == id)
+    }
+
+    /// Record `snapshot` as its target's pre-install original.
+    pub fn mark_original(&self, snapshot: &Snapshot) -> Result<(), String> {
+        let path = self.target_dir(&snapshot.target).join(ORIGINAL_POINTER);
+        write_atomic(&path, snapshot.stem())
+    }
+
+    /// The snapshot recorded as `target`'s pre-install original.
+    pub fn original(&self, target: &str) -> Option<Snapshot> {
+        let stem = fs::read_to_string(self.target_dir(target).join(ORIGINAL_POINTER)).ok()?;
+        self.snapshots(target)
+            .into_iter()
+            .find(|s| s.stem() == stem.trim())
+    }
+
+    /// Delete all but the newest `keep` snapshots of each target, never an original.
+    pub fn prune(&self, keep: usize) -> Result<Vec<Snapshot>, String> {
+        let mut removed = Vec::new();
+        for target in self.targets() {
+            let original = self.original(&target);
+            for snapshot in self.snapshots(&target).into_iter().skip(keep) {
+                if Some(&snapshot) == original.as_ref() {
+                    continue;
+                }
+                fs::remove_file(&snapshot.path)
+                    .map_err(|e| format!("Failed to delete {}: {}", snapshot.path.display(), e))?;
+                removed.push(snapshot);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Parse `<created>-<hash>.html` in `target`'s directory.
+fn parse_snapshot(target: &str, path: PathBuf) -> Option<Snapshot> {
+    let name = path.file_name()?.to_str()?;
+    let (created, hash) = name.strip_suffix(".html")?.split_once('-')?;
+    if hash.len() != HASH_PREFIX_LEN || !hash.chars().all(|c| c.is_ascii_hexdigit()) || !path.is_file() {
+        return None;
+    }
+    Some(Snapshot {
+        target: target.to_string(),
+        created: created.parse().ok()?,
+        hash: hash.to_string(),
+        path,
+    })
+}
+
+fn collect_targets(root: &Path, dir: &Path, targets: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut has_snapshots = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_targets(root, &path, targets);
+        } else {
+            has_snapshots |= parse_snapshot("", path).is_some();
+        }
+    }
+    if has_snapshots && let Ok(relative) = dir.strip_prefix(root) {
+        let parts: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        targets.push(parts.join("/"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str) -> BackupStore {
+        let root = std::env::temp_dir().join(format!("uprooted-backups-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        BackupStore::at(root)
+    }
+
+    #[test]
+    fn snapshots_dedupe_by_hash_and_round_trip() {
+        let store = store("dedupe");
+        let original = b"<html>\r\n<head></head>\n</html>\n\n";
+        let first = store.snapshot("WebRtcBundle/index.html", original).unwrap();
+        let again = store.snapshot("WebRtcBundle/index.html", original).unwrap();
+        assert_eq!(first, again);
+        assert_eq!(store.snapshots("WebRtcBundle/index.html").len(), 1);
+        assert_eq!(first.read().unwrap(), original);
+
+        store.snapshot("RootApps/chess/index.html", b"<html></html>").unwrap();
+        assert_eq!(store.targets(), ["RootApps/chess/index.html", "WebRtcBundle/index.html"]);
+        assert_eq!(store.find(&first.id()), Some(first.clone()));
+        assert_eq!(store.find("WebRtcBundle/index.html/0-0000000000000000"), None);
+
+        fs::write(&first.path, "tampered").unwrap();
+        assert!(first.read().unwrap_err().contains("corrupt"));
+        fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_newest_and_original() {
+        let store = store("prune");
+        let target = "WebRtcBundle/index.html";
+        let dir = store.target_dir(target);
+        fs::create_dir_all(&dir).unwrap();
+        // Fake creation times so the order is deterministic
+        for (created, content) in [(100, "a"), (200, "b"), (300, "c"), (400, "d")] {
+            let hash = &hash::sha256_hex(content.as_bytes())[..HASH_PREFIX_LEN];
+            fs::write(dir.join(format!("{}-{}.html", created, hash)), content).unwrap();
+        }
+        let oldest = store.snapshots(target).pop().unwrap();
+        assert_eq!(oldest.created, 100);
+        store.mark_original(&oldest).unwrap();
+
+        let removed = store.prune(2).unwrap();
+        assert_eq!(removed.iter().map(|s| s.created).collect::<Vec<_>>(), [200]);
+        let left: Vec<u64> = store.snapshots(target).iter().map(|s| s.created).collect();
+        assert_eq!(left, [400, 300, 100]);
+        assert_eq!(store.original(target), Some(oldest));
+        fs::remove_dir_all(&store.root).unwrap();
+    }
+}
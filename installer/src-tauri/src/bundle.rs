@@ -0,0 +1,473 @@
+//! Portable settings bundles (`uprooted settings export` / `import`).
+//!
+//! A bundle is a single JSON file holding `uprooted-settings.json`, the hook's
+//! `uprooted-settings.ini`, the stylesheets managed by `uprooted css` and every local
+//! stylesheet `customCss` pulls in by `file://` URL, so a setup can be carried to another
+//! machine or profile. Imported `customCss` stylesheets are written to `css/` in the
+//! uprooted dir and `customCss` is pointed at them.
+
+use crate::fsutil::write_atomic;
+use crate::hook::get_uprooted_dir;
+use crate::ini::Ini;
+use crate::settings::{self, UprootedSettings};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `format` of every bundle, so an arbitrary JSON file isn't taken for one.
+const BUNDLE_FORMAT: &str = "uprooted-settings-bundle";
+/// Bundle layout version written by this installer.
+const BUNDLE_VERSION: u32 = 1;
+
+/// How an imported bundle combines with the settings already there.
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ImportMode {
+    /// Keep current settings the bundle doesn't mention; the bundle wins where both have one.
+    Merge,
+    /// Make the settings exactly the bundle's.
+    Replace,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    format: String,
+    version: u32,
+    /// Seconds since the Unix epoch.
+    created: u64,
+    settings: Value,
+    /// Text of the INI file; `None` when the hook hadn't written one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ini: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    stylesheets: Vec<Stylesheet>,
+    /// Stylesheets managed by `uprooted css`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    user_stylesheets: Vec<UserStylesheet>,
+    /// Files a settings reset deletes that `settings` doesn't hold: the settings file when
+    /// it doesn't validate (`settings` is then the defaults), settings files set aside as
+    /// invalid and saved settings profiles. Only written before a reset, never imported.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    reset_files: Vec<RawFile>,
+}
+
+/// A file kept byte for byte, by its path relative to the profile directory.
+#[derive(Serialize, Deserialize)]
+struct RawFile {
+    path: String,
+    content: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UserStylesheet {
+    name: String,
+    content: String,
+}
+
+/// A local stylesheet referenced from `customCss`.
+#[derive(Serialize, Deserialize)]
+struct Stylesheet {
+    /// URL as written in `customCss`.
+    url: String,
+    /// File name it is written as on import, unique within the bundle.
+    name: String,
+    content: String,
+}
+
+/// What importing a bundle will write; nothing is written until every part checks out.
+pub struct Import {
+    pub settings: UprootedSettings,
+    /// `None` leaves the INI file alone.
+    pub ini: Option<Ini>,
+    pub stylesheets: Vec<(PathBuf, String)>,
+    /// Managed stylesheets the bundle doesn't have (replace mode only).
+    pub removed_stylesheets: Vec<PathBuf>,
+    /// Warnings from validating the imported settings.
+    pub warnings: Vec<String>,
+}
+
+impl Import {
+    /// Write the stylesheets and INI file; settings are left to the caller, which also
+    /// refreshes the patched HTML.
+    pub fn write_files(&self) -> Result<(), String> {
+        for (path, content) in &self.stylesheets {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            }
+            write_atomic(path, content)?;
+        }
+        for path in &self.removed_stylesheets {
+            fs::remove_file(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+        }
+        match &self.ini {
+            Some(ini) => crate::ini_settings::save_ini(ini),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Directory stylesheets imported with `customCss` are written to.
+pub fn imported_css_dir() -> PathBuf {
+    get_uprooted_dir().join("css")
+}
+
+/// Directory repair exports settings to before resetting them.
+pub fn repair_export_dir() -> PathBuf {
+    get_uprooted_dir().join("settings-exports")
+}
+
+/// Bundle the current settings. Returns it with one warning per referenced stylesheet
+/// that couldn't be read (the reference is kept, the file isn't bundled).
+pub fn export() -> Result<(Bundle, Vec<String>), String> {
+    let current = settings::load_settings();
+    let ini_path = crate::ini_settings::ini_path();
+    let ini = match fs::read_to_string(&ini_path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to read {}: {}", ini_path.display(), e)),
+    };
+
+    let mut warnings = Vec::new();
+    let mut stylesheets: Vec<Stylesheet> = Vec::new();
+    for url in stylesheet_urls(&current.custom_css) {
+        let Some(path) = crate::patcher::file_url_path(&url) else {
+            continue;
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                warnings.push(format!("Not bundling {}: {}", path.display(), e));
+                continue;
+            }
+        };
+        let file_name = path.file_name().map_or("custom.css".into(), |n| n.to_string_lossy());
+        let mut name = file_name.to_string();
+        for n in 2.. {
+            if !stylesheets.iter().any(|s| s.name == name) {
+                break;
+            }
+            name = format!("{}-{}", n, file_name);
+        }
+        stylesheets.push(Stylesheet { url, name, content });
+    }
+
+    let mut user_stylesheets = Vec::new();
+    for sheet in crate::stylesheets::list() {
+        match sheet.read() {
+            Ok(content) => user_stylesheets.push(UserStylesheet { name: sheet.name, content }),
+            Err(e) => warnings.push(format!("Not bundling {}", e)),
+        }
+    }
+
+    let bundle = Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        created: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        settings: serde_json::to_value(&current).map_err(|e| format!("Failed to serialize: {}", e))?,
+        ini,
+        stylesheets,
+        user_stylesheets,
+        reset_files: vec![],
+    };
+    Ok((bundle, warnings))
+}
+
+pub fn save_bundle(bundle: &Bundle, path: &Path) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(bundle).map_err(|e| format!("Failed to serialize: {}", e))?;
+    write_atomic(path, json)
+}
+
+pub fn load_bundle(path: &Path) -> Result<Bundle, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let bundle: Bundle = serde_json::from_str(&content)
+        .map_err(|e| format!("{} is not a settings bundle: {}", path.display(), e))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(format!("{} is not a settings bundle (format {:?})", path.display(), bundle.format));
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!(
+            "{} was exported by a newer Uprooted (bundle version {}, this installer reads {})",
+            path.display(),
+            bundle.version,
+            BUNDLE_VERSION
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Export the current settings to `settings-exports/` before repair resets them, along
+/// with every file the reset deletes that the settings can't carry.
+///
+/// `None` when there is nothing to lose. Fails rather than leave a file out, so the reset
+/// doesn't go ahead.
+pub fn export_before_reset() -> Result<Option<PathBuf>, String> {
+    let reset_files = files_lost_on_reset(&settings::settings_path(), &crate::profiles::profiles_dir())?;
+    if !settings::settings_path().exists() && !crate::ini_settings::ini_path().exists() && reset_files.is_empty() {
+        return Ok(None);
+    }
+    let (mut bundle, _) = export()?;
+    bundle.reset_files = reset_files;
+    let dir = repair_export_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("before-repair-{}.json", bundle.created));
+    save_bundle(&bundle, &path)?;
+    Ok(Some(path))
+}
+
+/// The invalid settings file, quarantined settings files and saved profiles next to
+/// `settings_file`, as they are on disk.
+fn files_lost_on_reset(settings_file: &Path, profiles_dir: &Path) -> Result<Vec<RawFile>, String> {
+    let Some(profile) = settings_file.parent() else {
+        return Ok(vec![]);
+    };
+    let mut paths = Vec::new();
+    match fs::read_to_string(settings_file) {
+        Ok(content) if settings::check_settings(&content).0.is_none() => paths.push(settings_file.to_path_buf()),
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {}: {}", settings_file.display(), e)),
+    }
+    paths.extend(settings::quarantined_files_in(profile));
+    match fs::read_dir(profiles_dir) {
+        Ok(entries) => {
+            let mut profiles: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+            profiles.sort();
+            paths.extend(profiles);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {}: {}", profiles_dir.display(), e)),
+    }
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let relative = path.strip_prefix(profile).unwrap_or(&path);
+            Ok(RawFile { path: relative.to_string_lossy().replace('\\', "/"), content })
+        })
+        .collect()
+}
+
+/// Work out what importing `bundle` over the current settings and INI file writes.
+pub fn prepare_import(bundle: &Bundle, mode: ImportMode) -> Result<Import, String> {
+    let current = settings::load_settings();
+    let current_ini = crate::ini_settings::load_ini()?;
+    let mut import = plan_import(bundle, mode, &current, current_ini, &imported_css_dir(), &crate::stylesheets::stylesheet_dir())?;
+    if mode == ImportMode::Replace {
+        import.removed_stylesheets = crate::stylesheets::list()
+            .into_iter()
+            .map(|sheet| sheet.path)
+            .filter(|path| !import.stylesheets.iter().any(|(written, _)| written == path))
+            .collect();
+    }
+    Ok(import)
+}
+
+fn plan_import(
+    bundle: &Bundle,
+    mode: ImportMode,
+    current: &UprootedSettings,
+    mut current_ini: Ini,
+    css_dir: &Path,
+    user_dir: &Path,
+) -> Result<Import, String> {
+    let mut incoming = bundle.settings.clone();
+    let mut stylesheets = Vec::new();
+    for sheet in &bundle.stylesheets {
+        if sheet.name.is_empty() || sheet.name.contains(['/', '\\']) || sheet.name.starts_with('.') {
+            return Err(format!("Invalid stylesheet name {:?} in bundle", sheet.name));
+        }
+        let path = css_dir.join(&sheet.name);
+        if let Some(Value::String(css)) = incoming.get_mut("customCss") {
+            *css = css.replace(&sheet.url, &crate::patcher::file_url(&path));
+        }
+        stylesheets.push((path, sheet.content.clone()));
+    }
+    for sheet in &bundle.user_stylesheets {
+        if !crate::stylesheets::valid_name(&sheet.name) {
+            return Err(format!("Invalid stylesheet name {:?} in bundle", sheet.name));
+        }
+        stylesheets.push((user_dir.join(&sheet.name), sheet.content.clone()));
+    }
+
+    let merged = match mode {
+        ImportMode::Replace => incoming,
+        ImportMode::Merge => {
+            let mut base = serde_json::to_value(current).map_err(|e| format!("Failed to serialize: {}", e))?;
+            merge(&mut base, incoming);
+            base
+        }
+    };
+    let (settings, report) = settings::check_settings(&merged.to_string());
+    let Some(settings) = settings else {
+        return Err(format!("Bundle settings are invalid: {}", report.errors.join("; ")));
+    };
+
+    let ini = bundle.ini.as_deref().map(|text| match mode {
+        ImportMode::Replace => Ini::parse(text),
+        ImportMode::Merge => {
+            for (section, key, value) in Ini::parse(text).entries() {
+                current_ini.set(section, key, value);
+            }
+            current_ini
+        }
+    });
+
+    Ok(Import { settings, ini, stylesheets, removed_stylesheets: vec![], warnings: report.warnings })
+}
+
+/// Copy `overlay` into `base`, recursing into objects present in both; anything else
+/// (arrays included) is replaced whole.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// `file://` URLs that `url(...)` tokens and `@import` strings in custom CSS point at, in
+/// order of first appearance. A quoted URL runs to its closing quote, so it may contain
+/// spaces; an unquoted `url(...)` runs to the closing paren.
+fn stylesheet_urls(css: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for (start, _) in css.match_indices("file://") {
+        let rest = &css[start..];
+        let before = css[..start].trim_end();
+        let end = match css[..start].chars().next_back() {
+            Some(quote @ ('"' | '\'')) => rest.find(quote),
+            _ if before.get(before.len().saturating_sub(4)..).is_some_and(|s| s.eq_ignore_ascii_case("url(")) => {
+                rest.find(')').map(|end| rest[..end].trim_end().len())
+            }
+            // Not a reference, e.g. a URL in a comment
+            _ => None,
+        };
+        let Some(url) = end.map(|end| rest[..end].to_string()) else {
+            continue;
+        };
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bundle() -> Bundle {
+        Bundle {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            created: 0,
+            settings: json!({
+                "version": 1,
+                "enabled": true,
+                "plugins": {"themes": {"enabled": true, "config": {"theme": "crimson"}}},
+                "customCss": "@import url(\"file:///old/home/dark.css\");\nbody { color: red; }"
+            }),
+            ini: Some("[Plugins]\nthemes=true\n".to_string()),
+            stylesheets: vec![Stylesheet {
+                url: "file:///old/home/dark.css".to_string(),
+                name: "dark.css".to_string(),
+                content: "body { background: #000; }".to_string(),
+            }],
+            user_stylesheets: vec![UserStylesheet { name: "compact.css".to_string(), content: "a { }".to_string() }],
+            reset_files: vec![],
+        }
+    }
+
+    fn current() -> UprootedSettings {
+        let mut current = UprootedSettings::default();
+        current.set("plugins.link-embeds.enabled", Value::Bool(true)).unwrap();
+        current.set("plugins.themes.config.accent", Value::from("#fff")).unwrap();
+        current
+    }
+
+    #[test]
+    fn stylesheets_are_found_and_relocated() {
+        assert_eq!(
+            stylesheet_urls("@import url(file:///a/x.css); @import 'file:///b/y.css';\n/* file:///a/x.css */"),
+            ["file:///a/x.css", "file:///b/y.css"]
+        );
+        assert_eq!(
+            stylesheet_urls("@import url(\"file:///home/Jane Doe/my theme.css\");\n@IMPORT URL( file:///c/z.css );\n@import \"file:///d/(1).css\";\n/* see file:///e/old.css */"),
+            ["file:///home/Jane Doe/my theme.css", "file:///c/z.css", "file:///d/(1).css"]
+        );
+
+        let import = plan_import(&bundle(), ImportMode::Replace, &current(), Ini::parse(""), Path::new("/new/css"), Path::new("/new/user")).unwrap();
+        assert_eq!(
+            import.stylesheets,
+            [
+                (PathBuf::from("/new/css/dark.css"), "body { background: #000; }".to_string()),
+                (PathBuf::from("/new/user/compact.css"), "a { }".to_string()),
+            ]
+        );
+        assert!(import.settings.custom_css.starts_with("@import url(\"file:///new/css/dark.css\");"));
+
+        let mut evil = bundle();
+        evil.stylesheets[0].name = "../uprooted-preload.js".to_string();
+        assert!(plan_import(&evil, ImportMode::Merge, &current(), Ini::parse(""), Path::new("/new/css"), Path::new("/new/user")).is_err());
+    }
+
+    #[test]
+    fn merge_keeps_what_the_bundle_lacks_and_replace_does_not() {
+        let ini = || Ini::parse("[Plugins]\nthemes=false\nMessageLogger=true\n");
+
+        let merged = plan_import(&bundle(), ImportMode::Merge, &current(), ini(), Path::new("/css"), Path::new("/user")).unwrap();
+        assert!(merged.settings.plugins["link-embeds"].enabled);
+        assert_eq!(merged.settings.plugins["themes"].config["accent"], "#fff");
+        assert_eq!(merged.settings.plugins["themes"].config["theme"], "crimson");
+        assert_eq!(merged.ini.unwrap().render(), "[Plugins]\nthemes=true\nMessageLogger=true\n");
+
+        let replaced = plan_import(&bundle(), ImportMode::Replace, &current(), ini(), Path::new("/css"), Path::new("/user")).unwrap();
+        assert!(!replaced.settings.plugins.contains_key("link-embeds"));
+        assert!(!replaced.settings.plugins["themes"].config.contains_key("accent"));
+        assert_eq!(replaced.ini.unwrap().render(), "[Plugins]\nthemes=true\n");
+
+        let mut broken = bundle();
+        broken.settings["enabled"] = json!("yes");
+        let err = plan_import(&broken, ImportMode::Merge, &current(), ini(), Path::new("/css"), Path::new("/user")).err().unwrap();
+        assert_eq!(err, "Bundle settings are invalid: enabled: expected true or false, found a string");
+    }
+
+    #[test]
+    fn reset_export_keeps_what_the_settings_cannot_hold() {
+        let dir = std::env::temp_dir().join(format!("uprooted-bundle-reset-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let settings_file = dir.join("uprooted-settings.json");
+        let profiles = dir.join("uprooted-profiles");
+        fs::create_dir_all(&profiles).unwrap();
+        fs::write(&settings_file, "{\"enabled\": \"yes\", \"plugins\": {").unwrap();
+        fs::write(dir.join("uprooted-settings.json.invalid-1700000000"), "not json").unwrap();
+        fs::write(profiles.join("daily.json"), "{\"enabled\": false}").unwrap();
+
+        // An invalid settings file is loaded as the defaults, so its text goes in as is
+        let files = files_lost_on_reset(&settings_file, &profiles).unwrap();
+        let kept: Vec<(&str, &str)> = files.iter().map(|f| (f.path.as_str(), f.content.as_str())).collect();
+        assert_eq!(
+            kept,
+            [
+                ("uprooted-settings.json", "{\"enabled\": \"yes\", \"plugins\": {"),
+                ("uprooted-settings.json.invalid-1700000000", "not json"),
+                ("uprooted-profiles/daily.json", "{\"enabled\": false}"),
+            ]
+        );
+
+        // A valid one is already in `settings`
+        fs::write(&settings_file, "{\"enabled\": false}").unwrap();
+        assert_eq!(files_lost_on_reset(&settings_file, &profiles).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
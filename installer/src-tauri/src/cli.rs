@@ -1,5 +1,11 @@
-use crate::{detection, hook, patcher};
+use crate::backups::BackupStore;
+use crate::bundle::{self, ImportMode};
+use crate::fsutil::write_atomic;
+use crate::ini_settings::{self, Source};
+use crate::{detection, hook, manifest, patcher, profiles, settings, stylesheets, targets, themes, watch};
+use crate::patcher::PatchOptions;
 use std::fs;
+use std::path::Path;
 
 // ANSI color codes
 const GREEN: &str = "\x1b[32m";
@@ -32,7 +38,7 @@ fn header(step: &str, title: &str) {
 // Plain-mode install (--plain)
 // ═══════════════════════════════════════════════════════════════════
 
-pub fn run_install_plain() {
+pub fn run_install_plain(patch: PatchOptions) {
     println!();
     println!(
         "{BOLD}  Uprooted v{} — Install{RESET}",
@@ -59,7 +65,12 @@ pub fn run_install_plain() {
 
     // Deploy files
     match hook::deploy_files() {
-        Ok(()) => ok("Files deployed"),
+        Ok(()) => {
+            ok("Files deployed");
+            if let Some(warning) = hook::profiler_manifest_warning() {
+                warn(warning);
+            }
+        }
         Err(e) => {
             fail(&format!("Deploy failed: {e}"));
             return;
@@ -76,9 +87,12 @@ pub fn run_install_plain() {
     }
 
     // Patch HTML (non-fatal: HTML files only exist after Root has been launched once)
-    let result = patcher::install();
+    let result = patcher::install(patch);
     if result.success {
         ok(&result.message);
+        for w in &result.warnings {
+            warn(w);
+        }
     } else {
         warn(&format!("{} Launch Root once, then re-run installer or --repair.", result.message));
     }
@@ -116,11 +130,14 @@ pub fn run_uninstall_plain() {
     let result = patcher::uninstall();
     if result.success {
         ok(&result.message);
+        for w in &result.warnings {
+            warn(w);
+        }
     } else {
         fail(&format!("HTML restore failed: {}", result.message));
     }
 
-    match hook::reset_settings() {
+    match hook::reset_settings(&[hook::UserData::All]) {
         Ok(n) => ok(&format!("Settings removed ({} file{} deleted)", n, if n == 1 { "" } else { "s" })),
         Err(e) => fail(&format!("Failed to remove settings: {e}")),
     }
@@ -139,11 +156,12 @@ pub fn run_uninstall_plain() {
 // Plain-mode repair (--repair --plain)
 // ═══════════════════════════════════════════════════════════════════
 
-pub fn run_repair_plain() {
+pub fn run_repair_plain(patch: PatchOptions, reset: &[hook::UserData]) {
     println!();
     println!(
-        "{BOLD}  Uprooted v{} — Repair (resets all settings){RESET}",
-        env!("CARGO_PKG_VERSION")
+        "{BOLD}  Uprooted v{} — Repair{}{RESET}",
+        env!("CARGO_PKG_VERSION"),
+        if reset.is_empty() { "" } else { " (resets settings)" }
     );
     println!("{DIM}  {}{RESET}", "═".repeat(40));
 
@@ -155,17 +173,38 @@ pub fn run_repair_plain() {
         ok("Root is not running");
     }
 
-    // Reset settings (plugins, themes, preferences)
-    match hook::reset_settings() {
-        Ok(n) => ok(&format!("Settings reset ({} file{} removed)", n, if n == 1 { "" } else { "s" })),
-        Err(e) => {
-            fail(&format!("Settings reset failed: {e}"));
-            return;
+    if reset.is_empty() {
+        ok("Settings kept (pass --reset-settings to reset them)");
+    } else {
+        // Export settings (plugins, themes, preferences) so the reset can be undone
+        if hook::UserData::includes_settings(reset) {
+            match bundle::export_before_reset() {
+                Ok(Some(path)) => ok(&format!("Settings exported to {}", path.display())),
+                Ok(None) => {}
+                Err(e) => {
+                    fail(&format!("Settings export failed, not resetting them: {e}"));
+                    return;
+                }
+            }
+        }
+
+        let what = hook::UserData::describe(reset);
+        match hook::reset_settings(reset) {
+            Ok(n) => ok(&format!("{what} reset ({} file{} removed)", n, if n == 1 { "" } else { "s" })),
+            Err(e) => {
+                fail(&format!("{what} reset failed: {e}"));
+                return;
+            }
         }
     }
 
     match hook::deploy_files() {
-        Ok(()) => ok("Files re-deployed"),
+        Ok(()) => {
+            ok("Files re-deployed");
+            if let Some(warning) = hook::profiler_manifest_warning() {
+                warn(warning);
+            }
+        }
         Err(e) => {
             fail(&format!("Deploy failed: {e}"));
             return;
@@ -180,9 +219,12 @@ pub fn run_repair_plain() {
         }
     }
 
-    let result = patcher::repair();
+    let result = patcher::repair(patch);
     if result.success {
         ok(&result.message);
+        for w in &result.warnings {
+            warn(w);
+        }
     } else {
         warn(&format!("{} Launch Root once, then re-run --repair.", result.message));
     }
@@ -192,11 +234,683 @@ pub fn run_repair_plain() {
     println!();
 }
 
+// ═══════════════════════════════════════════════════════════════════
+// Backups (backups list / restore / prune)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_backups_list() {
+    let store = BackupStore::open();
+    let targets = store.targets();
+    if targets.is_empty() {
+        warn("No snapshots yet (they are taken when Uprooted patches an HTML file)");
+        return;
+    }
+    for target in targets {
+        println!();
+        println!("  {BOLD}{target}{RESET}");
+        let original = store.original(&target);
+        for snapshot in store.snapshots(&target) {
+            let size = fs::metadata(&snapshot.path).map(|m| format_size(m.len())).unwrap_or_default();
+            let note = if Some(&snapshot) == original.as_ref() { " (pre-install original)" } else { "" };
+            println!(
+                "    {}  {DIM}{}  {}{RESET}{note}",
+                snapshot.id(),
+                format_timestamp(snapshot.created),
+                size
+            );
+        }
+    }
+    println!();
+}
+
+pub fn run_backups_restore(id: &str) {
+    let Some(snapshot) = BackupStore::open().find(id) else {
+        fail(&format!("No snapshot {id} (see `uprooted backups list`)"));
+        return;
+    };
+    let data = match snapshot.read() {
+        Ok(data) => data,
+        Err(e) => {
+            fail(&e);
+            return;
+        }
+    };
+    let target = snapshot
+        .target
+        .split('/')
+        .fold(detection::get_profile_dir(), |dir, part| dir.join(part));
+    match write_atomic(&target, &data) {
+        Ok(()) => ok(&format!("Restored {} from {}", target.display(), format_timestamp(snapshot.created))),
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_backups_prune(keep: usize) {
+    match BackupStore::open().prune(keep) {
+        Ok(removed) => {
+            for snapshot in &removed {
+                println!("    {DIM}removed {}{RESET}", snapshot.id());
+            }
+            ok(&format!("Pruned {} snapshot(s), kept the newest {keep} per target", removed.len()));
+        }
+        Err(e) => fail(&e),
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════
+// Settings (config get / set / unset, plugin list / enable / disable)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_config_get(path: &str) {
+    match settings::load_settings().get(path) {
+        Ok(Some(serde_json::Value::String(s))) => println!("{s}"),
+        Ok(Some(value)) => println!("{}", serde_json::to_string_pretty(&value).unwrap_or_default()),
+        Ok(None) => fail(&format!("No setting at {path}")),
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_config_set(path: &str, raw: &str) {
+    // Anything that isn't valid JSON is taken as a string, so `set ... crimson` works unquoted
+    let value = serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.to_string()));
+    let mut current = load_settings_for_edit();
+    match current.set(path, value) {
+        Ok(()) => save_and_refresh(&current, &format!("Set {path}")),
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_config_unset(path: &str) {
+    let mut current = load_settings_for_edit();
+    match current.unset(path) {
+        Ok(true) => save_and_refresh(&current, &format!("Unset {path}")),
+        Ok(false) => warn(&format!("No setting at {path}, nothing to unset")),
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_plugin_list() {
+    let current = settings::load_settings();
+    let mut names: Vec<&str> = manifest::PLUGINS.iter().map(|p| p.name).collect();
+    let mut others: Vec<&str> = current
+        .plugins
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !names.contains(name))
+        .collect();
+    others.sort();
+    names.extend(others);
+
+    println!();
+    for name in names {
+        let description = manifest::find(name).map_or("not a built-in plugin", |p| p.description);
+        if current.plugins.get(name).is_some_and(|p| p.enabled) {
+            println!("  {GREEN}\u{2713}{RESET} {BOLD}{name}{RESET}  {DIM}{description}{RESET}");
+        } else {
+            println!("  {DIM}-{RESET} {name}  {DIM}{description}{RESET}");
+        }
+    }
+    println!();
+}
+
+pub fn run_plugin_toggle(name: &str, enabled: bool) {
+    let mut current = load_settings_for_edit();
+    if manifest::find(name).is_none() && !current.plugins.contains_key(name) {
+        fail(&format!("Unknown plugin {name} (see `uprooted plugin list`)"));
+        return;
+    }
+    if current.plugins.get(name).is_some_and(|p| p.enabled == enabled) {
+        ok(&format!("{name} is already {}", if enabled { "enabled" } else { "disabled" }));
+        return;
+    }
+    match current.set(&format!("plugins.{name}.enabled"), serde_json::Value::Bool(enabled)) {
+        Ok(()) => save_and_refresh(&current, &format!("{} {name}", if enabled { "Enabled" } else { "Disabled" })),
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_plugin_lint(fix: bool) {
+    let mut current = load_settings_for_edit();
+    let fixed = if fix { manifest::fix(&mut current) } else { vec![] };
+    for finding in &fixed {
+        warn(&finding.issue.to_string());
+        println!("    {GREEN}{}{RESET}", finding.fix_description().unwrap_or_default());
+    }
+    let findings = manifest::lint(&current);
+    for finding in &findings {
+        warn(&finding.issue.to_string());
+        match finding.fix_description() {
+            Some(description) => println!("    {DIM}--fix: {description}{RESET}"),
+            None => println!("    {DIM}not fixable automatically, edit the settings file{RESET}"),
+        }
+    }
+
+    let fixable = findings.iter().filter(|f| f.fix.is_some()).count();
+    if !fixed.is_empty() {
+        save_and_refresh(&current, &format!("Applied {} fix(es)", fixed.len()));
+    } else if findings.is_empty() {
+        ok("Plugin settings match the installed plugins");
+    } else if fixable > 0 {
+        println!();
+        println!("  {DIM}Run `uprooted plugin lint --fix` to apply the fixes{RESET}");
+    }
+}
+
+pub fn run_config_validate() {
+    let path = settings::settings_path();
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            if print_settings_report(&content) {
+                ok(&format!("{} is valid", path.display()));
+            }
+        }
+        Err(_) => println!("  {DIM}No settings file at {} (defaults in use){RESET}", path.display()),
+    }
+
+    let quarantined = settings::quarantined_files();
+    if let Some(newest) = quarantined.first() {
+        println!();
+        warn(&format!(
+            "{} settings file(s) were set aside as invalid; fix one and move it back to {}",
+            quarantined.len(),
+            path.display()
+        ));
+        for file in &quarantined {
+            println!("    {DIM}{}{RESET}", file.display());
+        }
+        if let Ok(content) = fs::read_to_string(newest) {
+            println!();
+            println!("  {BOLD}{}:{RESET}", newest.display());
+            print_settings_report(&content);
+        }
+    }
+}
+
+pub fn run_config_diff() {
+    let ini = match ini_settings::load_ini() {
+        Ok(ini) => ini,
+        Err(e) => {
+            fail(&e);
+            return;
+        }
+    };
+    let differences = ini_settings::differences(&ini, &settings::load_settings());
+    let show = |value: &Option<serde_json::Value>| value.as_ref().map_or("-".to_string(), ToString::to_string);
+    let mut disagree = 0;
+    for d in &differences {
+        if d.ini.is_some() && d.json.is_some() {
+            disagree += 1;
+            warn(&format!("{}: ini {} / json {}", d.path, show(&d.ini), show(&d.json)));
+        } else {
+            println!("    {DIM}{}: ini {} / json {}{RESET}", d.path, show(&d.ini), show(&d.json));
+        }
+    }
+    if disagree == 0 {
+        ok("uprooted-settings.ini and uprooted-settings.json agree on every setting they share");
+    } else {
+        warn(&format!(
+            "{disagree} setting(s) disagree; `uprooted config sync --from ini` or `--from json` picks a side"
+        ));
+    }
+}
+
+pub fn run_config_sync(from: Source) {
+    let mut ini = match ini_settings::load_ini() {
+        Ok(ini) => ini,
+        Err(e) => {
+            fail(&e);
+            return;
+        }
+    };
+    let mut current = load_settings_for_edit();
+    match from {
+        Source::Ini => {
+            let (changed, problems) = ini_settings::sync_to_json(&ini, &mut current);
+            for p in &problems {
+                warn(&format!("Skipped {p}"));
+            }
+            if changed == 0 {
+                ok("uprooted-settings.json already matches the INI file");
+            } else {
+                save_and_refresh(&current, &format!("Copied {changed} setting(s) into uprooted-settings.json"));
+            }
+        }
+        Source::Json => {
+            let (changed, skipped) = ini_settings::sync_to_ini(&current, &mut ini);
+            for s in &skipped {
+                warn(&format!("Skipped {s}"));
+            }
+            if changed == 0 {
+                if skipped.is_empty() {
+                    ok("uprooted-settings.ini already matches the JSON file");
+                }
+                return;
+            }
+            match ini_settings::save_ini(&ini) {
+                Ok(()) => ok(&format!("Copied {changed} setting(s) into uprooted-settings.ini")),
+                Err(e) => fail(&e),
+            }
+        }
+    }
+}
+
+/// Print what [`settings::check_settings`] finds; `true` when there are no errors.
+fn print_settings_report(content: &str) -> bool {
+    let (_, report) = settings::check_settings(content);
+    if let Some(version) = report.version
+        && version < settings::SETTINGS_VERSION
+    {
+        println!(
+            "    {DIM}Schema version {version}, migrated to {} on the next save{RESET}",
+            settings::SETTINGS_VERSION
+        );
+    }
+    for e in &report.errors {
+        fail(e);
+    }
+    for w in &report.warnings {
+        warn(w);
+    }
+    report.errors.is_empty()
+}
+
+/// Load settings to edit, saying so if the file was unusable and has been set aside.
+fn load_settings_for_edit() -> settings::UprootedSettings {
+    set_aside_invalid_settings("; starting from defaults");
+    settings::load_settings()
+}
+
+/// Move an unusable settings file out of the way of a save, saying where it went.
+fn set_aside_invalid_settings(note: &str) {
+    if let Some(path) = settings::quarantine_invalid_settings() {
+        warn(&format!(
+            "Settings file was invalid and has been moved to {} (see `uprooted config validate`){note}",
+            path.display()
+        ));
+    }
+}
+
+/// Save edited settings and carry them into the patched HTML.
+fn save_and_refresh(new_settings: &settings::UprootedSettings, done: &str) {
+    // Recompile user stylesheets, so customCss always matches the stylesheet directory
+    let mut new_settings = new_settings.clone();
+    let mut sheet_warnings = Vec::new();
+    stylesheets::apply(&mut new_settings, &mut sheet_warnings);
+    for w in &sheet_warnings {
+        warn(w);
+    }
+    if let Err(e) = settings::save_settings(&new_settings) {
+        fail(&e);
+        return;
+    }
+    ok(done);
+    let result = patcher::refresh_settings();
+    if !result.success {
+        fail(&format!("Settings saved, but refreshing the patched HTML failed: {}", result.message));
+    } else if !result.files_patched.is_empty() {
+        ok(&format!("{} Restart Root to apply.", result.message));
+    } else if !detection::check_is_installed(&detection::find_target_html_files()) {
+        println!("    {DIM}Uprooted isn't installed; the settings apply once it is{RESET}");
+    }
+    for w in &result.warnings {
+        warn(w);
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════
+// User stylesheets (css list / add / remove)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_css_list() {
+    let sheets = stylesheets::list();
+    let mode = match settings::load_settings().stylesheet_injection {
+        settings::StylesheetInjection::CustomCss => "compiled into customCss",
+        settings::StylesheetInjection::Link => "injected as separate tags",
+    };
+    println!();
+    if sheets.is_empty() {
+        println!("  {DIM}No stylesheets in {} (add one with `uprooted css add <file>`){RESET}", stylesheets::stylesheet_dir().display());
+    }
+    for sheet in &sheets {
+        match sheet.read().and_then(|css| stylesheets::check_braces(&css).map(|()| css)) {
+            Ok(css) => println!("  {GREEN}\u{2713}{RESET} {BOLD}{}{RESET}  {DIM}{}{RESET}", sheet.name, format_size(css.len() as u64)),
+            Err(e) => fail(&format!("{}: {e} (not injected)", sheet.name)),
+        }
+    }
+    println!();
+    println!("  {DIM}Stylesheets are {mode}; change with `uprooted config set stylesheetInjection custom-css|link`{RESET}");
+    println!();
+}
+
+pub fn run_css_add(file: &Path) {
+    match stylesheets::add(file) {
+        Ok((name, replaced)) => {
+            save_and_refresh(&load_settings_for_edit(), &format!("{} {name}", if replaced { "Updated" } else { "Added" }))
+        }
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_css_remove(name: &str) {
+    match stylesheets::remove(name) {
+        Ok(name) => save_and_refresh(&load_settings_for_edit(), &format!("Removed {name}")),
+        Err(e) => fail(&e),
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════
+// Themes (theme list / set / preview / import)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_theme_list() {
+    let current = themes::selected(&settings::load_settings());
+    println!();
+    let all = themes::builtin().into_iter().map(|t| (t, true)).chain(themes::imported().into_iter().map(|t| (t, false)));
+    for (theme, builtin) in all {
+        let swatches: String = ["background", "accent", "text", "border"]
+            .iter()
+            .map(|key| swatch(theme.preview_colors.get(*key).map_or("", String::as_str)))
+            .collect();
+        let origin = if builtin { "" } else { " (imported)" };
+        if theme.name == current {
+            println!(
+                "  {GREEN}\u{2713}{RESET} {swatches} {BOLD}{}{RESET} {DIM}{}{origin} — {}{RESET}",
+                theme.name, theme.display_name, theme.description
+            );
+        } else {
+            println!("  {DIM}-{RESET} {swatches} {} {DIM}{}{origin} — {}{RESET}", theme.name, theme.display_name, theme.description);
+        }
+    }
+    println!();
+}
+
+pub fn run_theme_set(name: &str, accent: Option<&str>, background: Option<&str>) {
+    let Some((theme, builtin)) = themes::find(name) else {
+        fail(&format!("Unknown theme {name} (see `uprooted theme list`)"));
+        return;
+    };
+    let mut current = load_settings_for_edit();
+    for (key, colour) in [("customAccent", accent), ("customBackground", background)] {
+        let Some(colour) = colour else {
+            continue;
+        };
+        if name != themes::CUSTOM_THEME {
+            fail(&format!("--accent and --background only apply to the {} theme", themes::CUSTOM_THEME));
+            return;
+        }
+        if themes::parse_hex(colour).is_none() {
+            fail(&format!("{colour} is not a #RRGGBB colour"));
+            return;
+        }
+        if let Err(e) = current.set(&format!("plugins.themes.config.{key}"), serde_json::Value::from(colour)) {
+            fail(&e);
+            return;
+        }
+    }
+    if let Err(e) = themes::select(&mut current, &theme, builtin) {
+        fail(&e);
+        return;
+    }
+    if !current.plugins.get("themes").is_some_and(|p| p.enabled) {
+        warn("The themes plugin is disabled; `uprooted plugin enable themes` to apply the theme");
+    }
+    save_and_refresh(&current, &format!("Theme set to {}", theme.display_name));
+}
+
+pub fn run_theme_preview(name: Option<&str>) {
+    let current = settings::load_settings();
+    let name = name.map_or_else(|| themes::selected(&current), str::to_string);
+    let Some((theme, builtin)) = themes::find(&name) else {
+        fail(&format!("Unknown theme {name} (see `uprooted theme list`)"));
+        return;
+    };
+    let mut colours = theme.preview_colors.clone();
+    if theme.name == themes::CUSTOM_THEME
+        && let Some(config) = current.plugins.get("themes").map(|p| &p.config)
+    {
+        for (key, setting) in [("accent", "customAccent"), ("background", "customBackground")] {
+            if let Some(colour) = config.get(setting).and_then(|v| v.as_str()) {
+                colours.insert(key.to_string(), colour.to_string());
+            }
+        }
+    }
+
+    println!();
+    println!("  {BOLD}{}{RESET} {DIM}({}{}){RESET}", theme.display_name, theme.name, if builtin { "" } else { ", imported" });
+    if !theme.description.is_empty() || !theme.author.is_empty() {
+        println!("  {DIM}{} — by {}{RESET}", theme.description, theme.author);
+    }
+    println!();
+    let rgb = |key: &str| colours.get(key).and_then(|c| themes::parse_hex(c));
+    if let (Some((br, bg, bb)), Some((tr, tg, tb))) = (rgb("background"), rgb("text")) {
+        let accent = rgb("accent").map_or(String::new(), |(r, g, b)| format!("\x1b[38;2;{r};{g};{b}m"));
+        println!("  \x1b[48;2;{br};{bg};{bb}m\x1b[38;2;{tr};{tg};{tb}m  Message text  {accent}{BOLD}@mention{RESET}\x1b[48;2;{br};{bg};{bb}m  {RESET}");
+        println!();
+    }
+    for key in ["background", "text", "accent", "border"] {
+        if let Some(colour) = colours.get(key) {
+            println!("  {} {key:<10} {DIM}{colour}{RESET}", swatch(colour));
+        }
+    }
+    if !theme.variables.is_empty() {
+        println!();
+        for (variable, value) in &theme.variables {
+            println!("  {} {variable} {DIM}{value}{RESET}", swatch(value));
+        }
+    }
+    println!();
+}
+
+pub fn run_theme_import(file: &Path) {
+    let (theme, warnings) = match themes::import(file) {
+        Ok(imported) => imported,
+        Err(e) => {
+            fail(&e);
+            return;
+        }
+    };
+    for w in &warnings {
+        warn(w);
+    }
+    ok(&format!("Imported {} ({})", theme.display_name, theme.name));
+    let mut current = load_settings_for_edit();
+    if themes::selected(&current) != theme.name {
+        println!("    {DIM}`uprooted theme set {}` to use it{RESET}", theme.name);
+        return;
+    }
+    // Already selected: carry the new variables into the settings
+    match themes::select(&mut current, &theme, false) {
+        Ok(()) => save_and_refresh(&current, "Updated the selected theme"),
+        Err(e) => fail(&e),
+    }
+}
+
+/// A block of `colour` (`#RRGGBB`), blank for anything else.
+fn swatch(colour: &str) -> String {
+    match themes::parse_hex(colour) {
+        Some((r, g, b)) => format!("\x1b[48;2;{r};{g};{b}m  {RESET}"),
+        None => "  ".to_string(),
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════
+// Settings bundles (settings export / import)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_settings_export(file: &Path) {
+    let (exported, warnings) = match bundle::export() {
+        Ok(exported) => exported,
+        Err(e) => {
+            fail(&e);
+            return;
+        }
+    };
+    for w in &warnings {
+        warn(w);
+    }
+    match bundle::save_bundle(&exported, file) {
+        Ok(()) => ok(&format!("Settings exported to {}", file.display())),
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_settings_import(file: &Path, mode: ImportMode) {
+    let import = match bundle::load_bundle(file).and_then(|b| bundle::prepare_import(&b, mode)) {
+        Ok(import) => import,
+        Err(e) => {
+            fail(&e);
+            return;
+        }
+    };
+    for w in &import.warnings {
+        warn(w);
+    }
+    if let Err(e) = import.write_files() {
+        fail(&e);
+        return;
+    }
+    for (path, _) in &import.stylesheets {
+        println!("    {DIM}wrote {}{RESET}", path.display());
+    }
+    if import.ini.is_some() {
+        println!("    {DIM}wrote {}{RESET}", ini_settings::ini_path().display());
+    }
+    let how = if mode == ImportMode::Merge { "merged into" } else { "replaced" };
+    set_aside_invalid_settings("");
+    save_and_refresh(&import.settings, &format!("Settings from {} {how} the current ones", file.display()));
+}
+
+// ═══════════════════════════════════════════════════════════════════
+// Settings profiles (profile list / save / switch / delete)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_profile_list() {
+    let names = profiles::list();
+    let current = settings::load_settings();
+    println!();
+    if names.is_empty() {
+        println!("  {DIM}No profiles in {} (save one with `uprooted profile save <name>`){RESET}", profiles::profiles_dir().display());
+    }
+    for name in &names {
+        match profiles::load(name) {
+            Ok(profile) if profiles::same(&profile, &current) => {
+                println!("  {GREEN}\u{25cf}{RESET} {BOLD}{name}{RESET}  {DIM}(current settings){RESET}")
+            }
+            Ok(_) => println!("  {DIM}\u{25cb}{RESET} {name}"),
+            Err(e) => fail(&e),
+        }
+    }
+    println!();
+}
+
+pub fn run_profile_save(name: &str) {
+    // Save what is in the file, not the defaults an invalid file would load as
+    let current = match fs::read_to_string(settings::settings_path()) {
+        Ok(content) => match settings::check_settings(&content) {
+            (Some(current), _) => current,
+            (None, _) => {
+                fail("Settings file is not valid, fix it first (see `uprooted config validate`)");
+                return;
+            }
+        },
+        Err(_) => settings::UprootedSettings::default(),
+    };
+    match profiles::save(name, &current) {
+        Ok(true) => ok(&format!("Profile {name} updated with the current settings")),
+        Ok(false) => ok(&format!("Current settings saved as profile {name}")),
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_profile_switch(name: &str) {
+    match profiles::load(name) {
+        Ok(profile) => {
+            set_aside_invalid_settings("");
+            save_and_refresh(&profile, &format!("Switched to profile {name}"))
+        }
+        Err(e) => fail(&e),
+    }
+}
+
+pub fn run_profile_delete(name: &str) {
+    match profiles::delete(name) {
+        Ok(()) => ok(&format!("Deleted profile {name}")),
+        Err(e) => fail(&e),
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════
+// Settings sync (watch)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_watch() {
+    println!(
+        "  {DIM}Watching {} (Ctrl+C to stop){RESET}",
+        settings::settings_path().display()
+    );
+    // Start in step, in case the file was edited while nothing was watching
+    report_sync(watch::sync_once());
+    watch::run(report_sync);
+}
+
+fn report_sync(event: watch::Event) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    print!("  {DIM}{}{RESET}", format_timestamp(now));
+    match event {
+        watch::Event::Synced(synced, warnings) => {
+            println!();
+            if synced.is_empty() {
+                ok("Patched HTML already matches the settings");
+            }
+            for (file, kind) in &synced {
+                let how = match kind {
+                    patcher::SyncKind::SettingsLine => "settings updated",
+                    patcher::SyncKind::Block => "injection re-patched",
+                };
+                ok(&format!("{file}: {how}"));
+            }
+            for w in &warnings {
+                warn(w);
+            }
+        }
+        watch::Event::Invalid(errors) => {
+            println!();
+            warn("Settings file not synced until it is valid:");
+            for e in &errors {
+                println!("    {DIM}{e}{RESET}");
+            }
+        }
+        watch::Event::Missing => {
+            println!();
+            warn("Settings file is missing; the injected settings are left as they are");
+        }
+        watch::Event::Failed(e) => {
+            println!();
+            fail(&format!("Sync failed: {e}"));
+        }
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════
+// Autostart (--autostart on|off)
+// ═══════════════════════════════════════════════════════════════════
+
+pub fn run_autostart(enabled: bool) {
+    match hook::set_autostart(enabled) {
+        Ok(()) if enabled => ok("Autostart enabled — Root will start with Uprooted at login"),
+        Ok(()) => ok("Autostart disabled"),
+        Err(e) => fail(&format!("Autostart change failed: {e}")),
+    }
+}
+
 // ═══════════════════════════════════════════════════════════════════
 // Diagnose (--diagnose) — verbose diagnostic output
 // ═══════════════════════════════════════════════════════════════════
 
-pub fn run_diagnose() {
+pub fn run_diagnose(patch: PatchOptions) {
     println!();
     println!(
         "{BOLD}  Uprooted v{} — Diagnostics{RESET}",
@@ -240,7 +954,44 @@ pub fn run_diagnose() {
                     .map(|(_, dir)| format!("{dir}/index.html"))
             })
             .unwrap_or_else(|| f.clone());
-        println!("    {DIM}{short}{RESET}");
+        match detection.patch_status.get(f) {
+            Some(status) => println!("    {DIM}{short} ({status}){RESET}"),
+            None => println!("    {DIM}{short}{RESET}"),
+        }
+    }
+
+    if let Err(e) = targets::load_rules() {
+        warn(&format!("{e} (using the built-in target rules)"));
+    }
+    if let Ok(ini) = ini_settings::load_ini() {
+        let disagree = ini_settings::differences(&ini, &settings::load_settings())
+            .iter()
+            .filter(|d| d.ini.is_some() && d.json.is_some())
+            .count();
+        if disagree > 0 {
+            warn(&format!("{disagree} setting(s) differ between the hook's INI and the JSON file (see `uprooted config diff`)"));
+        }
+    }
+    // An unusable file is only set aside by the next edit; until then defaults are in use
+    if let Ok(content) = fs::read_to_string(settings::settings_path())
+        && settings::check_settings(&content).0.is_none()
+    {
+        warn("Settings file is invalid, so defaults are in use (see `uprooted config validate`)");
+    }
+    if let Some(newest) = settings::quarantined_files().first() {
+        warn(&format!("Invalid settings file set aside as {} (see `uprooted config validate`)", newest.display()));
+    }
+    let unmatched = detection::find_unmatched_html_files();
+    if !unmatched.is_empty() {
+        warn(&format!(
+            "{} HTML file(s) match no target rule (add them to {} to patch them):",
+            unmatched.len(),
+            targets::user_rules_path().display()
+        ));
+        for f in &unmatched {
+            let short = f.strip_prefix(&profile_dir).unwrap_or(f);
+            println!("    {DIM}{}{RESET}", short.display());
+        }
     }
 
     if detection.is_installed {
@@ -248,6 +999,18 @@ pub fn run_diagnose() {
     } else {
         warn("HTML patches NOT detected (not installed)");
     }
+    let count = |wanted| detection.patch_status.values().filter(|s| **s == wanted).count();
+    let outdated = count(patcher::PatchStatus::Outdated);
+    if outdated > 0 {
+        warn(&format!("{outdated} HTML patch(es) outdated (older installer, or settings changed since; --repair refreshes them)"));
+    }
+    let foreign = count(patcher::PatchStatus::Foreign);
+    if foreign > 0 {
+        warn(&format!("{foreign} HTML patch(es) edited since install (--repair leaves them; uninstall and reinstall to replace)"));
+    }
+    for mismatch in &detection.integrity_mismatches {
+        warn(&format!("Integrity mismatch: {mismatch} (Root will refuse to load it; --repair re-deploys)"));
+    }
 
     // Hook status detail
     let hs = &detection.hook_status;
@@ -261,10 +1024,17 @@ pub fn run_diagnose() {
 
     println!();
     println!("  {BOLD}Env var status:{RESET}");
+    println!("    {DIM}Profiler GUID: {}{RESET}", hook::profiler_manifest().guid);
+    if let Some(warning) = hook::profiler_manifest_warning() {
+        warn(warning);
+    }
     status_line("DOTNET_ENABLE_PROFILING", hs.env_enable_profiling);
     status_line("DOTNET_PROFILER", hs.env_profiler_guid);
     status_line("DOTNET_PROFILER_PATH", hs.env_profiler_path);
     status_line("DOTNET_ReadyToRun", hs.env_ready_to_run);
+    if hs.env_profiler_outdated {
+        warn("Env vars use an older profiler GUID (outdated install — re-run installer or --repair)");
+    }
 
     if hs.env_vars_active {
         ok("Env vars active in current session");
@@ -272,6 +1042,19 @@ pub fn run_diagnose() {
         warn("Env vars NOT active in current session (re-login may be needed)");
     }
 
+    if cfg!(target_os = "linux") {
+        if hs.url_handler {
+            ok("root:// links open Root through Uprooted");
+        } else {
+            warn("root:// links bypass Uprooted (another handler is the default)");
+        }
+        if hs.autostart {
+            ok("Autostart at login enabled (--autostart off to disable)");
+        } else {
+            println!("    {DIM}Autostart at login disabled (--autostart on to enable){RESET}");
+        }
+    }
+
     // ── [2/7] Process check ──
     header("2/7", "Process check");
     if hook::check_root_running() {
@@ -288,8 +1071,8 @@ pub fn run_diagnose() {
             ok("Files deployed successfully");
             let dir = hook::get_uprooted_dir();
             let files = [
-                "uprooted_profiler.dll",
-                "libuprooted_profiler.so",
+                hook::profiler_manifest().filename.as_str(),
+                "profiler.json",
                 "UprootedHook.dll",
                 "UprootedHook.deps.json",
                 "uprooted-preload.js",
@@ -333,11 +1116,20 @@ pub fn run_diagnose() {
 
     // ── [5/7] HTML patching ──
     header("5/7", "HTML patching");
-    let patch_result = patcher::install();
+    let patch_result = patcher::install(patch);
     if patch_result.success {
         ok(&patch_result.message);
-        for f in &patch_result.files_patched {
-            println!("    {DIM}{f}{RESET}");
+        let mut targets: Vec<_> = patch_result.profiles.iter().collect();
+        targets.sort();
+        for (f, profile) in targets {
+            if patch_result.files_patched.contains(f) {
+                println!("    {DIM}{f} (profile: {profile}){RESET}");
+            } else {
+                println!("    {DIM}{f} (profile: {profile}, nothing injected){RESET}");
+            }
+        }
+        for w in &patch_result.warnings {
+            warn(w);
         }
     } else {
         fail(&format!("HTML patching FAILED: {}", patch_result.message));
@@ -367,9 +1159,20 @@ pub fn run_diagnose() {
     } else {
         fail("HTML patches missing");
     }
+    if final_detection.integrity_mismatches.is_empty() {
+        ok("Deployed files match the patch's integrity hashes");
+    } else {
+        for mismatch in &final_detection.integrity_mismatches {
+            fail(&format!("Integrity mismatch: {mismatch}"));
+        }
+    }
 
     // Overall verdict
-    let all_good = deploy_ok && final_hs.files_ok && final_hs.env_ok && final_detection.is_installed;
+    let all_good = deploy_ok
+        && final_hs.files_ok
+        && final_hs.env_ok
+        && final_detection.is_installed
+        && final_detection.integrity_mismatches.is_empty();
     println!();
     if all_good {
         println!(
@@ -434,22 +1237,21 @@ fn format_size(bytes: u64) -> String {
 fn chrono_lite() -> String {
     use std::time::SystemTime;
     match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-        Ok(d) => {
-            let secs = d.as_secs();
-            let days = secs / 86400;
-            let time_secs = secs % 86400;
-            let hours = time_secs / 3600;
-            let minutes = (time_secs % 3600) / 60;
-            let seconds = time_secs % 60;
-            let (year, month, day) = days_to_date(days);
-            format!(
-                "{year:04}-{month:02}-{day:02} {hours:02}:{minutes:02}:{seconds:02} UTC"
-            )
-        }
+        Ok(d) => format_timestamp(d.as_secs()),
         Err(_) => "unknown".to_string(),
     }
 }
 
+fn format_timestamp(secs: u64) -> String {
+    let days = secs / 86400;
+    let time_secs = secs % 86400;
+    let hours = time_secs / 3600;
+    let minutes = (time_secs % 3600) / 60;
+    let seconds = time_secs % 60;
+    let (year, month, day) = days_to_date(days);
+    format!("{year:04}-{month:02}-{day:02} {hours:02}:{minutes:02}:{seconds:02} UTC")
+}
+
 fn days_to_date(days: u64) -> (u64, u64, u64) {
     let z = days + 719468;
     let era = z / 146097;
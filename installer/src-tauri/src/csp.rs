@@ -0,0 +1,491 @@
+//! Content-Security-Policy checks for the injection block.
+//!
+//! Root's pages can declare a policy with `<meta http-equiv="Content-Security-Policy">`.
+//! One that doesn't allow `file:` or inline scripts blocks the injection without any error,
+//! so every policy in force at the injection block is checked against the elements we
+//! inject. In amend mode blocking policies get sources for exactly those elements, never a
+//! whole scheme: `'sha256-...'` for inline ones, the pinned `'sha384-...'` of a script's
+//! `integrity` (CSP3 matches hashes against SRI-checked external scripts) and the exact URL
+//! of anything else. The original value is kept on the meta tag in `data-uprooted-csp` and
+//! put back by [`restore`] when the injection is stripped.
+
+use crate::hash;
+use crate::html::{self, Tag};
+use std::ops::Range;
+
+/// Attribute holding the original `content` of an amended meta tag.
+const ORIGINAL_ATTR: &str = "data-uprooted-csp";
+
+const SCRIPT_DIRECTIVES: &[&str] = &["script-src-elem", "script-src", "default-src"];
+const STYLE_DIRECTIVES: &[&str] = &["style-src-elem", "style-src", "default-src"];
+
+/// What to do about a policy that blocks the injection.
+#[derive(Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum CspMode {
+    /// Leave the policy alone and report what it blocks.
+    #[default]
+    Report,
+    /// Allow exactly the injected elements (hash and exact-URL sources); undone on uninstall.
+    Amend,
+}
+
+/// Result of checking one document.
+pub struct CspCheck {
+    /// The document, with amended policies in amend mode.
+    pub html: String,
+    /// One line per injected element a policy still blocks.
+    pub blocked: Vec<String>,
+    /// Number of policies amended.
+    pub amended: usize,
+}
+
+/// A `<meta http-equiv="Content-Security-Policy">` tag.
+struct MetaPolicy {
+    tag_start: usize,
+    policy: String,
+    /// Raw `content` value, including its quotes.
+    value_span: Range<usize>,
+    /// Span of the `data-uprooted-csp` attribute and the original raw value it holds.
+    original: Option<(Range<usize>, String)>,
+}
+
+fn find_meta_policies(html: &str) -> Vec<MetaPolicy> {
+    let mut policies = Vec::new();
+    for tag in html::start_tags(html).filter(|t| t.name == "meta") {
+        let attrs = html::attributes(html, &tag);
+        let value = |name: &str| attrs.iter().find(|a| a.name == name);
+
+        let is_csp = value("http-equiv")
+            .and_then(|a| a.value.as_deref())
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("content-security-policy"));
+        let Some(content) = value("content").filter(|_| is_csp) else {
+            continue;
+        };
+        let (Some(policy), Some(value_span)) = (content.value.clone(), content.value_span.clone())
+        else {
+            continue;
+        };
+        let original = value(ORIGINAL_ATTR)
+            .and_then(|a| Some((a.span.clone(), a.value.clone()?)));
+
+        policies.push(MetaPolicy { tag_start: tag.start, policy, value_span, original });
+    }
+    policies
+}
+
+/// A serialized policy's directives, in order; later duplicates are ignored as browsers do.
+struct Policy {
+    directives: Vec<(String, Vec<String>)>,
+}
+
+impl Policy {
+    fn parse(policy: &str) -> Self {
+        let mut directives: Vec<(String, Vec<String>)> = Vec::new();
+        for directive in policy.split(';') {
+            let mut tokens = directive.split_ascii_whitespace();
+            let Some(name) = tokens.next().map(str::to_ascii_lowercase) else {
+                continue;
+            };
+            if directives.iter().all(|(n, _)| *n != name) {
+                directives.push((name, tokens.map(str::to_string).collect()));
+            }
+        }
+        Policy { directives }
+    }
+
+    fn sources(&self, name: &str) -> Option<&[String]> {
+        self.directives
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, s)| s.as_slice())
+    }
+
+    /// The directive governing `resource`, if any.
+    fn effective(&self, resource: &Resource) -> Option<(&'static str, &[String])> {
+        resource
+            .directives()
+            .iter()
+            .find_map(|name| Some((*name, self.sources(name)?)))
+    }
+
+    fn allows(&self, resource: &Resource) -> bool {
+        self.effective(resource)
+            .is_none_or(|(_, sources)| resource.allowed_by(sources))
+    }
+
+    /// Add `resource`'s source to the directive governing it.
+    ///
+    /// A `default-src` fallback is copied into a new `script-src` / `style-src` first, so
+    /// everything else `default-src` covers keeps the original sources.
+    fn allow(&mut self, resource: &Resource) {
+        let Some((name, _)) = self.effective(resource) else {
+            return;
+        };
+        let name = if name == "default-src" {
+            let specific = resource.directives()[1];
+            let sources = self.sources("default-src").unwrap_or_default().to_vec();
+            self.directives.push((specific.to_string(), sources));
+            specific
+        } else {
+            name
+        };
+        if let Some((_, sources)) = self.directives.iter_mut().find(|(n, _)| n == name) {
+            let source = resource.source(sources);
+            // 'none' is only valid as the sole source
+            sources.retain(|s| !s.eq_ignore_ascii_case("'none'"));
+            if !sources.iter().any(|s| s.eq_ignore_ascii_case(&source)) {
+                sources.push(source);
+            }
+        }
+    }
+
+    fn serialize(&self) -> String {
+        self.directives
+            .iter()
+            .map(|(name, sources)| {
+                std::iter::once(name.as_str())
+                    .chain(sources.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// An element in the injection block that a policy can block.
+enum Resource {
+    InlineScript { label: String, text: String },
+    InlineStyle { text: String },
+    /// `integrity` is the pinned `sha384-...` value, if the tag has one.
+    Script { url: String, integrity: Option<String> },
+    Stylesheet { url: String },
+}
+
+impl Resource {
+    fn is_script(&self) -> bool {
+        matches!(self, Resource::InlineScript { .. } | Resource::Script { .. })
+    }
+
+    /// Directives that govern this element, most specific first.
+    fn directives(&self) -> &'static [&'static str] {
+        if self.is_script() { SCRIPT_DIRECTIVES } else { STYLE_DIRECTIVES }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Resource::InlineScript { label, .. } => label.clone(),
+            Resource::InlineStyle { .. } => "inline theme <style>".to_string(),
+            Resource::Script { url, .. } => format!("<script src=\"{}\">", url),
+            Resource::Stylesheet { url } => format!("<link href=\"{}\">", url),
+        }
+    }
+
+    /// The source that allows exactly this element in a directive with `sources`.
+    fn source(&self, sources: &[String]) -> String {
+        match self {
+            Resource::InlineScript { text, .. } | Resource::InlineStyle { text } => {
+                format!("'sha256-{}'", hash::sha256_base64(normalize_newlines(text).as_bytes()))
+            }
+            // A first hash would switch off the page's own 'unsafe-inline'; the URL doesn't
+            Resource::Script { integrity: Some(integrity), .. }
+                if !relies_on_unsafe_inline(sources) && integrity.starts_with("sha384-") =>
+            {
+                format!("'{}'", integrity)
+            }
+            Resource::Script { url, .. } | Resource::Stylesheet { url } => url_source(url),
+        }
+    }
+
+    fn allowed_by(&self, sources: &[String]) -> bool {
+        let has = |wanted: &str| sources.iter().any(|s| s.eq_ignore_ascii_case(wanted));
+        // 'strict-dynamic' makes script-src ignore 'unsafe-inline', 'self' and scheme sources
+        let strict_dynamic = self.is_script() && has("'strict-dynamic'");
+        match self {
+            Resource::InlineScript { .. } | Resource::InlineStyle { .. } => {
+                let own_hash = self.source(sources);
+                // Any nonce or hash source disables 'unsafe-inline'
+                let hashes_or_nonces = sources.iter().any(|s| is_hash_or_nonce(s));
+                sources.contains(&own_hash)
+                    || (has("'unsafe-inline'") && !hashes_or_nonces && !strict_dynamic)
+            }
+            // Root loads its pages from file://, where Chromium lets 'self' match any file: URL
+            Resource::Script { url, integrity } => {
+                integrity.as_ref().is_some_and(|i| has(&format!("'{}'", i)))
+                    || (!strict_dynamic && (has("file:") || has("'self'") || has(&url_source(url))))
+            }
+            Resource::Stylesheet { url } => has("file:") || has("'self'") || has(&url_source(url)),
+        }
+    }
+}
+
+/// Whether inline elements get through `sources` by 'unsafe-inline' alone.
+fn relies_on_unsafe_inline(sources: &[String]) -> bool {
+    sources.iter().any(|s| s.eq_ignore_ascii_case("'unsafe-inline'")) && !sources.iter().any(|s| is_hash_or_nonce(s))
+}
+
+/// A URL as a source expression: characters that separate sources or directives are
+/// percent-encoded.
+fn url_source(url: &str) -> String {
+    url.replace('%', "%25").replace(' ', "%20").replace('\t', "%09").replace(';', "%3B").replace(',', "%2C")
+}
+
+fn is_hash_or_nonce(source: &str) -> bool {
+    let lower = source.to_ascii_lowercase();
+    ["'nonce-", "'sha256-", "'sha384-", "'sha512-"]
+        .iter()
+        .any(|prefix| lower.starts_with(prefix))
+}
+
+/// Browsers hash script and style text after newline normalization.
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Elements in the injection block that policies apply to.
+fn injected_resources(block: &str) -> Vec<Resource> {
+    let mut resources = Vec::new();
+    for tag in html::start_tags(block) {
+        let attrs = html::attributes(block, &tag);
+        let value = |name: &str| {
+            attrs
+                .iter()
+                .find(|a| a.name == name)
+                .and_then(|a| a.value.clone())
+        };
+        match tag.name.as_str() {
+            "script" => match value("src") {
+                Some(url) => resources.push(Resource::Script { url, integrity: value("integrity") }),
+                None => {
+                    let text = element_text(block, &tag);
+                    let label = if let Some(plugin) = value("data-uprooted-plugin") {
+                        format!("inline {} plugin <script>", plugin)
+                    } else if text.contains("__UPROOTED_SETTINGS__") {
+                        "inline settings <script>".to_string()
+                    } else {
+                        "inline preload <script>".to_string()
+                    };
+                    resources.push(Resource::InlineScript { label, text });
+                }
+            },
+            "style" => resources.push(Resource::InlineStyle { text: element_text(block, &tag) }),
+            "link" if value("rel").is_some_and(|r| r.eq_ignore_ascii_case("stylesheet")) => {
+                if let Some(url) = value("href") {
+                    resources.push(Resource::Stylesheet { url });
+                }
+            }
+            _ => {}
+        }
+    }
+    resources
+}
+
+fn element_text(html: &str, tag: &Tag) -> String {
+    html::raw_text(html, tag).unwrap_or_default().to_string()
+}
+
+/// Check the policies in force at `block` (the injection block's byte range in `html`).
+///
+/// Only meta tags before the block apply to it. In amend mode a blocking policy is rewritten
+/// to allow the injected elements, keeping the original for [`restore`].
+pub fn check(html: &str, block: Range<usize>, mode: CspMode) -> CspCheck {
+    let resources = injected_resources(&html[block.clone()]);
+    let mut blocked = Vec::new();
+    let mut edits = Vec::new();
+
+    for meta in find_meta_policies(html) {
+        if meta.tag_start >= block.start {
+            continue;
+        }
+        let mut policy = Policy::parse(&meta.policy);
+        let blocking: Vec<&Resource> = resources.iter().filter(|r| !policy.allows(r)).collect();
+        if blocking.is_empty() {
+            continue;
+        }
+
+        if mode == CspMode::Amend {
+            for resource in &blocking {
+                policy.allow(resource);
+            }
+            edits.push((meta.value_span.clone(), format!("\"{}\"", encode_attribute(&policy.serialize()))));
+            if meta.original.is_none() {
+                let original = &html[meta.value_span.clone()];
+                edits.push((
+                    meta.value_span.end..meta.value_span.end,
+                    format!(" {}=\"{}\"", ORIGINAL_ATTR, encode_attribute(original)),
+                ));
+            }
+        }
+
+        for resource in resources.iter().filter(|r| !policy.allows(r)) {
+            let directive = policy.effective(resource).map_or("default-src", |(name, _)| name);
+            blocked.push(format!("CSP {} blocks {}", directive, resource.describe()));
+        }
+    }
+
+    CspCheck {
+        amended: edits.iter().filter(|(span, _)| !span.is_empty()).count(),
+        html: apply_edits(html, edits),
+        blocked,
+    }
+}
+
+/// Put back every policy amended by [`check`]; other documents are returned unchanged.
+pub fn restore(html: &str) -> String {
+    let mut edits = Vec::new();
+    for meta in find_meta_policies(html) {
+        let Some((attr_span, original)) = meta.original else {
+            continue;
+        };
+        // Take the separating space with the attribute
+        let attr_start = if html[..attr_span.start].ends_with(|c: char| c.is_ascii_whitespace()) {
+            attr_span.start - 1
+        } else {
+            attr_span.start
+        };
+        edits.push((attr_start..attr_span.end, String::new()));
+        edits.push((meta.value_span, original));
+    }
+    apply_edits(html, edits)
+}
+
+/// Whether [`check`] has amended any policy in `html`.
+pub fn is_amended(html: &str) -> bool {
+    find_meta_policies(html).iter().any(|meta| meta.original.is_some())
+}
+
+/// Replace non-overlapping byte ranges, applying the last one first so offsets stay valid.
+fn apply_edits(html: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(span, _)| std::cmp::Reverse((span.start, span.end)));
+    let mut result = html.to_string();
+    for (span, text) in edits {
+        result.replace_range(span, &text);
+    }
+    result
+}
+
+fn encode_attribute(value: &str) -> String {
+    value.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: &str = "<!-- uprooted:start -->\n    <script>window.__UPROOTED_SETTINGS__={};</script>\n    <script src=\"file:///home/u/uprooted-preload.js\" integrity=\"sha384-cHJlbG9hZA==\"></script>\n    <link rel=\"stylesheet\" href=\"file:///home/u/uprooted.css\">\n    <!-- uprooted:end -->";
+
+    fn document(meta: &str) -> (String, Range<usize>) {
+        let head = format!("<!doctype html>\n<html>\n<head>\n  {}\n  <title>Root</title>\n    ", meta);
+        let html = format!("{}{}\n  </head>\n<body></body>\n</html>\n", head, BLOCK);
+        (html, head.len()..head.len() + BLOCK.len())
+    }
+
+    #[test]
+    fn no_policy_allows_everything() {
+        let (html, block) = document("<meta charset=\"utf-8\">");
+        let result = check(&html, block, CspMode::Report);
+        assert!(result.blocked.is_empty());
+        assert_eq!(result.html, html);
+    }
+
+    #[test]
+    fn reports_blocked_inline_and_file_sources() {
+        let (html, block) = document(
+            r#"<meta http-equiv="Content-Security-Policy" content="default-src 'self' https:; script-src 'nonce-abc' https:">"#,
+        );
+        let result = check(&html, block, CspMode::Report);
+        assert_eq!(result.amended, 0);
+        assert_eq!(result.html, html);
+        assert_eq!(
+            result.blocked,
+            [
+                "CSP script-src blocks inline settings <script>",
+                "CSP script-src blocks <script src=\"file:///home/u/uprooted-preload.js\">",
+            ]
+        );
+    }
+
+    #[test]
+    fn unsafe_inline_is_disabled_by_nonces() {
+        let (html, block) = document(
+            r#"<meta http-equiv="content-security-policy" content="script-src 'unsafe-inline' file:">"#,
+        );
+        assert!(check(&html, block, CspMode::Report).blocked.is_empty());
+
+        let (html, block) = document(
+            r#"<meta http-equiv="content-security-policy" content="script-src 'unsafe-inline' 'nonce-x' file:">"#,
+        );
+        assert_eq!(check(&html, block, CspMode::Report).blocked.len(), 1);
+    }
+
+    #[test]
+    fn policies_after_the_block_do_not_apply() {
+        let (html, _) = document("");
+        let meta = r#"<meta http-equiv="Content-Security-Policy" content="default-src 'none'">"#;
+        let html = html.replace("<body>", &format!("<body>{}", meta));
+        let block = html.find("<!-- uprooted:start").unwrap()..html.find("</head>").unwrap();
+        assert!(check(&html, block, CspMode::Report).blocked.is_empty());
+    }
+
+    #[test]
+    fn amend_allows_exactly_the_injection_and_restores() {
+        let meta = r#"<meta http-equiv="Content-Security-Policy" content="default-src 'none'; img-src data:; style-src 'self' 'unsafe-inline'">"#;
+        let (html, block) = document(meta);
+        let result = check(&html, block, CspMode::Amend);
+        assert!(result.blocked.is_empty(), "{:?}", result.blocked);
+        assert_eq!(result.amended, 1);
+
+        let amended = find_meta_policies(&result.html).remove(0);
+        let settings_hash = hash::sha256_base64(b"window.__UPROOTED_SETTINGS__={};");
+        assert_eq!(
+            amended.policy,
+            format!("default-src 'none'; img-src data:; style-src 'self' 'unsafe-inline'; script-src 'sha256-{}' 'sha384-cHJlbG9hZA=='", settings_hash)
+        );
+        assert!(amended.original.is_some());
+
+        // Nothing but the injection gets through
+        let policy = Policy::parse(&amended.policy);
+        let other_script = Resource::Script { url: "file:///home/u/other.js".to_string(), integrity: None };
+        let other_inline = Resource::InlineScript { label: String::new(), text: "alert(1)".to_string() };
+        assert!(!policy.allows(&other_script) && !policy.allows(&other_inline));
+
+        // Re-checking the amended document finds nothing to do
+        let block = result.html.find("<!-- uprooted:start").unwrap()..result.html.find("</head>").unwrap();
+        let again = check(&result.html, block, CspMode::Amend);
+        assert_eq!((again.amended, again.blocked.len()), (0, 0));
+
+        assert_eq!(restore(&result.html), html);
+    }
+
+    #[test]
+    fn amend_allows_stylesheets_and_unpinned_scripts_by_exact_url() {
+        let meta = r#"<meta http-equiv="Content-Security-Policy" content="script-src 'unsafe-inline'; style-src 'none'">"#;
+        let (html, block) = document(meta);
+        let result = check(&html, block, CspMode::Amend);
+        assert!(result.blocked.is_empty(), "{:?}", result.blocked);
+
+        // A hash would disable 'unsafe-inline' for the page's own scripts, so the URL is used
+        let amended = find_meta_policies(&result.html).remove(0);
+        assert_eq!(
+            amended.policy,
+            "script-src 'unsafe-inline' file:///home/u/uprooted-preload.js; style-src file:///home/u/uprooted.css"
+        );
+        let policy = Policy::parse(&amended.policy);
+        let other_sheet = Resource::Stylesheet { url: "file:///home/u/other.css".to_string() };
+        let other_script = Resource::Script { url: "file:///tmp/x.js".to_string(), integrity: Some("sha384-eA==".to_string()) };
+        assert!(!policy.allows(&other_sheet) && !policy.allows(&other_script));
+
+        assert_eq!(url_source("file:///home/J Doe/a;b.css"), "file:///home/J%20Doe/a%3Bb.css");
+    }
+
+    #[test]
+    fn restore_is_byte_exact_for_odd_quoting() {
+        let meta = "<META HTTP-EQUIV='Content-Security-Policy' CONTENT='script-src \"x\" &amp; file:' >";
+        let (html, block) = document(meta);
+        let result = check(&html, block, CspMode::Amend);
+        assert_eq!(result.amended, 1);
+        assert_ne!(result.html, html);
+        assert_eq!(restore(&result.html), html);
+        assert_eq!(restore(&html), html);
+    }
+}
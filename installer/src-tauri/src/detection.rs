@@ -1,9 +1,12 @@
+use crate::backups::BackupStore;
 use crate::hook::{self, HookStatus};
 use serde::Serialize;
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
 use crate::patcher;
+use crate::targets::{self, TargetRules};
 
 #[derive(Serialize, Clone)]
 pub struct DetectionResult {
@@ -12,6 +15,10 @@ pub struct DetectionResult {
     pub profile_dir: String,
     pub html_files: Vec<String>,
     pub is_installed: bool,
+    /// Each target's injection compared with what this installer would write, keyed by path.
+    pub patch_status: HashMap<String, patcher::PatchStatus>,
+    /// Files referenced by a patch whose contents no longer match its `integrity` hashes.
+    pub integrity_mismatches: Vec<String>,
     pub hook_status: HookStatus,
 }
 
@@ -145,12 +152,11 @@ pub fn get_root_exe_path() -> PathBuf {
             let exe_link = entry.path().join("exe");
             if let Ok(exe) = fs::read_link(&exe_link) {
                 let exe_str = exe.to_string_lossy().to_lowercase();
-                if (exe_str.contains("root") && exe_str.contains("appimage"))
-                    || exe_str.ends_with("/root")
+                if ((exe_str.contains("root") && exe_str.contains("appimage"))
+                    || exe_str.ends_with("/root"))
+                    && exe.is_file()
                 {
-                    if exe.is_file() {
-                        return exe;
-                    }
+                    return exe;
                 }
             }
         }
@@ -160,13 +166,12 @@ pub fn get_root_exe_path() -> PathBuf {
     if let Ok(output) = std::process::Command::new("which")
         .arg("Root")
         .output()
+        && output.status.success()
     {
-        if output.status.success() {
-            let path_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            let p = PathBuf::from(&path_str);
-            if p.is_file() {
-                return p;
-            }
+        let path_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let p = PathBuf::from(&path_str);
+        if p.is_file() {
+            return p;
         }
     }
 
@@ -182,43 +187,92 @@ pub fn get_root_exe_path() -> PathBuf {
     PathBuf::from(format!("{}/Applications/Root.app/Contents/MacOS/Root", home))
 }
 
+/// The target rules, or the shipped defaults and a warning if the user's rule file is invalid.
+pub fn target_rules() -> (TargetRules, Option<String>) {
+    match targets::load_rules() {
+        Ok(rules) => (rules, None),
+        Err(e) => (TargetRules::defaults(), Some(format!("{}; using the shipped target rules", e))),
+    }
+}
+
+/// HTML files to patch, per the target rules (shipped defaults if the user's file is invalid).
 pub fn find_target_html_files() -> Vec<PathBuf> {
+    target_rules().0.find_targets(&get_profile_dir())
+}
+
+/// The targets plus every file the backup store holds snapshots of, so files patched under
+/// earlier rules are still refreshed.
+pub fn find_known_html_files() -> Vec<PathBuf> {
     let profile = get_profile_dir();
-    let mut targets = Vec::new();
+    let mut files = find_target_html_files();
+    files.extend(
+        BackupStore::open()
+            .targets()
+            .iter()
+            .map(|target| target.split('/').fold(profile.clone(), |path, part| path.join(part)))
+            .filter(|path| path.is_file()),
+    );
+    files.sort();
+    files.dedup();
+    files
+}
 
-    // WebRtcBundle/index.html
-    let webrtc_index = profile.join("WebRtcBundle").join("index.html");
-    if webrtc_index.exists() {
-        targets.push(webrtc_index);
-    }
+/// Everything uninstall has to visit: [`find_known_html_files`] and any HTML file in the
+/// profile carrying an uprooted marker, whatever the rules say now.
+///
+/// Walks the whole profile, like [`find_unmatched_html_files`].
+pub fn find_installed_html_files() -> Vec<PathBuf> {
+    let mut files = find_known_html_files();
+    files.extend(
+        targets::find_html_files(&get_profile_dir())
+            .into_iter()
+            .filter(|file| fs::read_to_string(file).is_ok_and(|content| patcher::is_patched(&content))),
+    );
+    files.sort();
+    files.dedup();
+    files
+}
 
-    // RootApps/*/index.html
-    let root_apps_dir = profile.join("RootApps");
-    if root_apps_dir.exists() {
-        if let Ok(entries) = fs::read_dir(&root_apps_dir) {
-            for entry in entries.flatten() {
-                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
-                    let app_index = entry.path().join("index.html");
-                    if app_index.exists() {
-                        targets.push(app_index);
-                    }
-                }
-            }
+/// HTML files in the profile that no target rule covers, e.g. a surface added by a Root update.
+///
+/// Walks the whole profile (Chromium caches included), so only `--diagnose` asks for it.
+pub fn find_unmatched_html_files() -> Vec<PathBuf> {
+    target_rules().0.find_unmatched(&get_profile_dir())
+}
+
+pub fn check_is_installed(html_files: &[PathBuf]) -> bool {
+    for file in html_files {
+        if let Ok(content) = fs::read_to_string(file)
+            && patcher::is_patched(&content)
+        {
+            return true;
         }
     }
-
-    targets
+    false
 }
 
-pub fn check_is_installed(html_files: &[PathBuf]) -> bool {
+pub fn check_integrity(html_files: &[PathBuf]) -> Vec<String> {
+    let mut mismatches = Vec::new();
     for file in html_files {
         if let Ok(content) = fs::read_to_string(file) {
-            if patcher::is_patched(&content) {
-                return true;
+            for mismatch in patcher::integrity_mismatches(&content) {
+                if !mismatches.contains(&mismatch) {
+                    mismatches.push(mismatch);
+                }
             }
         }
     }
-    false
+    mismatches
+}
+
+pub fn check_patch_status(html_files: &[PathBuf]) -> HashMap<String, patcher::PatchStatus> {
+    html_files
+        .iter()
+        .filter_map(|file| {
+            let content = fs::read_to_string(file).ok()?;
+            Some((file.to_string_lossy().to_string(), patcher::patch_status(file, &content)))
+        })
+        .collect()
 }
 
 pub fn detect() -> DetectionResult {
@@ -226,6 +280,8 @@ pub fn detect() -> DetectionResult {
     let profile = get_profile_dir();
     let html_files = find_target_html_files();
     let is_installed = check_is_installed(&html_files);
+    let patch_status = check_patch_status(&html_files);
+    let integrity_mismatches = check_integrity(&html_files);
     let hook_status = hook::check_hook_status();
 
     DetectionResult {
@@ -237,6 +293,8 @@ pub fn detect() -> DetectionResult {
             .map(|p| p.to_string_lossy().to_string())
             .collect(),
         is_installed,
+        patch_status,
+        integrity_mismatches,
         hook_status,
     }
 }
@@ -0,0 +1,122 @@
+//! Crash-safe file writes.
+//!
+//! Everything the installer writes (patched HTML, backups, settings, launch scripts,
+//! env and desktop files) goes through [`write_atomic`], so an interrupted run leaves
+//! either the old file or the new one, never a truncated mix.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replace `path` with `data` atomically.
+///
+/// Writes a temp file in the same directory, fsyncs it, gives it the permissions of the
+/// file it replaces, renames it over the original and fsyncs the directory. A symlink is
+/// written through, so dotfiles managed as links stay links.
+pub fn write_atomic(path: &Path, data: impl AsRef<[u8]>) -> Result<(), String> {
+    let path = resolve_symlink(path);
+    let tmp_path = temp_path(&path)?;
+
+    let result = write_temp(&path, &tmp_path, data.as_ref()).and_then(|()| {
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    sync_parent(&path);
+    Ok(())
+}
+
+fn write_temp(path: &Path, tmp_path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut file = File::create(tmp_path)
+        .map_err(|e| format!("Failed to create {}: {}", tmp_path.display(), e))?;
+    file.write_all(data)
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())
+            .map_err(|e| format!("Failed to copy permissions of {}: {}", path.display(), e))?;
+    }
+    file.sync_all()
+        .map_err(|e| format!("Failed to flush {}: {}", tmp_path.display(), e))
+}
+
+/// The file a symlink points at, or `path` itself.
+fn resolve_symlink(path: &Path) -> PathBuf {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+fn temp_path(path: &Path) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Failed to write {}: not a file path", path.display()))?;
+    Ok(path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    )))
+}
+
+/// Persist the rename; not every filesystem supports syncing a directory, so best-effort.
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent()
+        && let Ok(dir) = File::open(if parent.as_os_str().is_empty() { Path::new(".") } else { parent })
+    {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("uprooted-fsutil-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replaces_content_without_leftovers() {
+        let dir = temp_dir("replace");
+        let path = dir.join("index.html");
+        write_atomic(&path, "first").unwrap();
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        assert!(write_atomic(&dir.join("missing/index.html"), "x").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn preserves_mode_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("mode");
+        let script = dir.join("launch-root.sh");
+        fs::write(&script, "old").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o750)).unwrap();
+        write_atomic(&script, "new").unwrap();
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o777, 0o750);
+
+        let link = dir.join(".profile");
+        std::os::unix::fs::symlink(&script, &link).unwrap();
+        write_atomic(&link, "through link").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&script).unwrap(), "through link");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,18 @@
 use crate::embedded;
-use serde::Serialize;
+use crate::fsutil::write_atomic;
+use crate::guid::is_valid_guid;
+#[cfg(unix)]
+use crate::quote;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 #[cfg(target_os = "windows")]
 use winreg::enums::*;
 #[cfg(target_os = "windows")]
 use winreg::RegKey;
 
-const PROFILER_GUID: &str = "{D1A6F5A0-1234-4567-89AB-CDEF01234567}";
-
 #[cfg(target_os = "windows")]
 const ENV_VARS: &[&str] = &[
     // DOTNET_ prefix (primary — .NET 10+)
@@ -35,6 +38,9 @@ pub struct HookStatus {
     pub theme_css: bool,
     pub env_enable_profiling: bool,
     pub env_profiler_guid: bool,
+    /// True if the env vars reference a valid profiler GUID other than the current one
+    /// (an install from a build that shipped a different profiler).
+    pub env_profiler_outdated: bool,
     pub env_profiler_path: bool,
     pub env_ready_to_run: bool,
     /// True if all files are deployed
@@ -44,6 +50,10 @@ pub struct HookStatus {
     /// True if env vars are active in the current process environment (Linux only).
     /// On Windows this always matches env_ok since registry changes apply immediately.
     pub env_vars_active: bool,
+    /// True if the Uprooted desktop entry is the default `root://` URL handler (Linux only).
+    pub url_handler: bool,
+    /// True if Root is started with Uprooted at login via XDG autostart (Linux only).
+    pub autostart: bool,
 }
 
 // ==================== Platform-specific: install directory ====================
@@ -69,14 +79,97 @@ pub fn get_uprooted_dir() -> PathBuf {
     PathBuf::from(home).join("Library/Application Support/uprooted")
 }
 
-// ==================== Platform-specific: profiler filename ====================
+// ==================== Profiler manifest ====================
 
-#[cfg(target_os = "windows")]
-const PROFILER_FILENAME: &str = "uprooted_profiler.dll";
-#[cfg(target_os = "linux")]
-const PROFILER_FILENAME: &str = "libuprooted_profiler.so";
-#[cfg(target_os = "macos")]
-const PROFILER_FILENAME: &str = "libuprooted_profiler.dylib";
+/// Env var naming a manifest file that replaces the built-in one, for testing
+/// alternate profiler builds without rebuilding the installer.
+const PROFILER_MANIFEST_ENV: &str = "UPROOTED_PROFILER_MANIFEST";
+
+/// Profiler identity: the CLSID registered with the CLR and the file it's deployed as.
+///
+/// Comes from `artifacts/profiler.json` at build time (see `build.rs`), or from the
+/// file named by `UPROOTED_PROFILER_MANIFEST` at runtime.
+#[derive(Serialize, Deserialize)]
+pub struct ProfilerManifest {
+    pub guid: String,
+    pub filename: String,
+    /// Profiler binary to deploy instead of the embedded one (runtime override only).
+    #[serde(default, skip_serializing)]
+    pub path: Option<PathBuf>,
+}
+
+fn resolved_profiler_manifest() -> &'static (ProfilerManifest, Option<String>) {
+    static MANIFEST: OnceLock<(ProfilerManifest, Option<String>)> = OnceLock::new();
+    MANIFEST.get_or_init(|| resolve_profiler_manifest(std::env::var(PROFILER_MANIFEST_ENV).ok().as_deref()))
+}
+
+/// The profiler manifest in effect for this run.
+pub fn profiler_manifest() -> &'static ProfilerManifest {
+    &resolved_profiler_manifest().0
+}
+
+/// Why the `UPROOTED_PROFILER_MANIFEST` override was ignored, if it was.
+pub fn profiler_manifest_warning() -> Option<&'static str> {
+    resolved_profiler_manifest().1.as_deref()
+}
+
+/// The manifest at `override_path` if it loads, else the built-in one with the reason.
+fn resolve_profiler_manifest(override_path: Option<&str>) -> (ProfilerManifest, Option<String>) {
+    let built_in = ProfilerManifest {
+        guid: env!("UPROOTED_PROFILER_GUID").to_string(),
+        filename: env!("UPROOTED_PROFILER_FILENAME").to_string(),
+        path: None,
+    };
+    let Some(override_path) = override_path else {
+        return (built_in, None);
+    };
+    match load_profiler_manifest(Path::new(override_path)) {
+        Ok(manifest) => (manifest, None),
+        Err(e) => (built_in, Some(format!("Ignoring {}: {}", PROFILER_MANIFEST_ENV, e))),
+    }
+}
+
+fn load_profiler_manifest(path: &Path) -> Result<ProfilerManifest, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let manifest: ProfilerManifest = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    if !is_valid_guid(&manifest.guid) {
+        return Err(format!("{:?} is not a valid profiler GUID", manifest.guid));
+    }
+    if manifest.filename.is_empty() || manifest.filename.contains(['/', '\\']) {
+        return Err(format!("{:?} is not a bare file name", manifest.filename));
+    }
+    Ok(manifest)
+}
+
+/// Check whether a configured GUID is the current profiler's.
+fn is_current_guid(guid: &str) -> bool {
+    same_guid(guid, &profiler_manifest().guid)
+}
+
+/// CLSIDs compare case-insensitively.
+fn same_guid(guid: &str, current: &str) -> bool {
+    is_valid_guid(guid) && guid.eq_ignore_ascii_case(current)
+}
+
+/// A well-formed GUID that isn't `current`: left behind by an older install.
+fn is_outdated_guid(guid: &str, current: &str) -> bool {
+    is_valid_guid(guid) && !same_guid(guid, current)
+}
+
+/// Extract the profiler GUID from a generated env file or shell script.
+#[cfg(unix)]
+fn parse_configured_guid(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let line = line.trim();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let value = line
+            .strip_prefix("DOTNET_PROFILER=")
+            .or_else(|| line.strip_prefix("CORECLR_PROFILER="))?;
+        Some(value.trim().trim_matches(['\'', '"']).to_string())
+    })
+}
 
 // ==================== Deploy files ====================
 
@@ -85,8 +178,22 @@ pub fn deploy_files() -> Result<(), String> {
     let dir = get_uprooted_dir();
     fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
 
+    let manifest = profiler_manifest();
+    let override_profiler = match &manifest.path {
+        Some(path) => Some(
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        ),
+        None => None,
+    };
+    let manifest_json = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize profiler manifest: {}", e))?;
+
     let files: &[(&str, &[u8])] = &[
-        (PROFILER_FILENAME, embedded::PROFILER),
+        (
+            &manifest.filename,
+            override_profiler.as_deref().unwrap_or(embedded::PROFILER),
+        ),
+        ("profiler.json", &manifest_json),
         ("UprootedHook.dll", embedded::HOOK_DLL),
         ("UprootedHook.deps.json", embedded::HOOK_DEPS_JSON),
         ("UprootedHook.net9.dll", embedded::HOOK_DLL_NET9),
@@ -99,15 +206,14 @@ pub fn deploy_files() -> Result<(), String> {
 
     for (name, data) in files {
         let path = dir.join(name);
-        fs::write(&path, data)
-            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        write_atomic(&path, data)?;
     }
 
     // On Unix, set the profiler shared library as executable
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
-        let profiler_path = dir.join(PROFILER_FILENAME);
+        let profiler_path = dir.join(&manifest.filename);
         let perms = std::fs::Permissions::from_mode(0o755);
         let _ = std::fs::set_permissions(&profiler_path, perms);
     }
@@ -125,8 +231,9 @@ pub fn set_env_vars() -> Result<(), String> {
         .create_subkey("Environment")
         .map_err(|e| format!("Failed to open HKCU\\Environment: {}", e))?;
 
+    let guid = profiler_manifest().guid.as_str();
     let profiler_path = get_uprooted_dir()
-        .join("uprooted_profiler.dll")
+        .join(&profiler_manifest().filename)
         .to_string_lossy()
         .to_string();
 
@@ -138,7 +245,7 @@ pub fn set_env_vars() -> Result<(), String> {
         .set_value("DOTNET_ENABLE_PROFILING", &"1")
         .map_err(|e| format!("Failed to set DOTNET_ENABLE_PROFILING: {}", e))?;
     env_key
-        .set_value("DOTNET_PROFILER", &PROFILER_GUID)
+        .set_value("DOTNET_PROFILER", &guid)
         .map_err(|e| format!("Failed to set DOTNET_PROFILER: {}", e))?;
     env_key
         .set_value("DOTNET_PROFILER_PATH", &profiler_path)
@@ -152,7 +259,7 @@ pub fn set_env_vars() -> Result<(), String> {
         .set_value("CORECLR_ENABLE_PROFILING", &"1")
         .map_err(|e| format!("Failed to set CORECLR_ENABLE_PROFILING: {}", e))?;
     env_key
-        .set_value("CORECLR_PROFILER", &PROFILER_GUID)
+        .set_value("CORECLR_PROFILER", &guid)
         .map_err(|e| format!("Failed to set CORECLR_PROFILER: {}", e))?;
     env_key
         .set_value("CORECLR_PROFILER_PATH", &profiler_path)
@@ -183,11 +290,11 @@ pub fn remove_env_vars() -> Result<(), String> {
 
 /// Check env var status from the registry (DOTNET_ primary, CORECLR_ fallback).
 #[cfg(target_os = "windows")]
-fn check_env_vars() -> (bool, bool, bool, bool) {
+fn check_env_vars() -> (bool, Option<String>, bool, bool) {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
     let env_key = match hkcu.open_subkey("Environment") {
         Ok(k) => k,
-        Err(_) => return (false, false, false, false),
+        Err(_) => return (false, None, false, false),
     };
 
     let enable: bool = env_key
@@ -195,11 +302,10 @@ fn check_env_vars() -> (bool, bool, bool, bool) {
         .or_else(|_| env_key.get_value::<String, _>("CORECLR_ENABLE_PROFILING"))
         .map(|v| v == "1")
         .unwrap_or(false);
-    let guid: bool = env_key
+    let guid: Option<String> = env_key
         .get_value::<String, _>("DOTNET_PROFILER")
         .or_else(|_| env_key.get_value::<String, _>("CORECLR_PROFILER"))
-        .map(|v| v == PROFILER_GUID)
-        .unwrap_or(false);
+        .ok();
     let path: bool = env_key
         .get_value::<String, _>("DOTNET_PROFILER_PATH")
         .or_else(|_| env_key.get_value::<String, _>("CORECLR_PROFILER_PATH"))
@@ -249,7 +355,7 @@ fn broadcast_env_change() {
 #[cfg(target_os = "linux")]
 pub fn set_env_vars() -> Result<(), String> {
     let dir = get_uprooted_dir();
-    let profiler_path = dir.join(PROFILER_FILENAME);
+    let profiler_path = dir.join(&profiler_manifest().filename);
     let root_path = crate::detection::get_root_exe_path();
 
     // 1. systemd environment.d -- session-wide env vars (like Windows registry)
@@ -270,11 +376,10 @@ DOTNET_ReadyToRun=0\n\
 CORECLR_ENABLE_PROFILING=1\n\
 CORECLR_PROFILER={guid}\n\
 CORECLR_PROFILER_PATH={path}\n",
-        guid = PROFILER_GUID,
-        path = profiler_path.display()
+        guid = quote::environment_d(&profiler_manifest().guid),
+        path = quote::environment_d(&profiler_path.to_string_lossy())
     );
-    fs::write(env_dir.join("uprooted.conf"), &env_conf)
-        .map_err(|e| format!("Failed to write environment.d/uprooted.conf: {}", e))?;
+    write_atomic(&env_dir.join("uprooted.conf"), &env_conf)?;
 
     // 2. Wrapper script -- works immediately from terminal
     let wrapper = dir.join("launch-root.sh");
@@ -284,20 +389,19 @@ CORECLR_PROFILER_PATH={path}\n",
 # .NET 10+ (DOTNET_ prefix)\n\
 export DOTNET_EnableDiagnostics=1\n\
 export DOTNET_ENABLE_PROFILING=1\n\
-export DOTNET_PROFILER='{guid}'\n\
-export DOTNET_PROFILER_PATH='{path}'\n\
+export DOTNET_PROFILER={guid}\n\
+export DOTNET_PROFILER_PATH={path}\n\
 export DOTNET_ReadyToRun=0\n\
 # Legacy (.NET 8/9)\n\
 export CORECLR_ENABLE_PROFILING=1\n\
-export CORECLR_PROFILER='{guid}'\n\
-export CORECLR_PROFILER_PATH='{path}'\n\
-exec '{root}' \"$@\"\n",
-        guid = PROFILER_GUID,
-        path = profiler_path.display(),
-        root = root_path.display()
+export CORECLR_PROFILER={guid}\n\
+export CORECLR_PROFILER_PATH={path}\n\
+exec {root} \"$@\"\n",
+        guid = quote::shell(&profiler_manifest().guid),
+        path = quote::shell(&profiler_path.to_string_lossy()),
+        root = quote::shell(&root_path.to_string_lossy())
     );
-    fs::write(&wrapper, &script)
-        .map_err(|e| format!("Failed to write wrapper script: {}", e))?;
+    write_atomic(&wrapper, &script)?;
 
     #[cfg(unix)]
     {
@@ -306,8 +410,10 @@ exec '{root}' \"$@\"\n",
         let _ = std::fs::set_permissions(&wrapper, perms);
     }
 
-    // 3. .desktop file
-    create_desktop_file(&wrapper)?;
+    // 3. .desktop file (icon is best-effort: a missing icon shouldn't block install)
+    let icon = install_root_icon(&root_path);
+    create_desktop_file(&wrapper, icon)?;
+    register_url_handler()?;
 
     // 4. KDE Plasma env script -- sourced on Plasma session startup
     let plasma_env_dir = PathBuf::from(&home).join(".config/plasma-workspace/env");
@@ -318,18 +424,18 @@ exec '{root}' \"$@\"\n",
 # .NET 10+ (DOTNET_ prefix)\n\
 export DOTNET_EnableDiagnostics=1\n\
 export DOTNET_ENABLE_PROFILING=1\n\
-export DOTNET_PROFILER='{guid}'\n\
-export DOTNET_PROFILER_PATH='{path}'\n\
+export DOTNET_PROFILER={guid}\n\
+export DOTNET_PROFILER_PATH={path}\n\
 export DOTNET_ReadyToRun=0\n\
 # Legacy (.NET 8/9)\n\
 export CORECLR_ENABLE_PROFILING=1\n\
-export CORECLR_PROFILER='{guid}'\n\
-export CORECLR_PROFILER_PATH='{path}'\n",
-        guid = PROFILER_GUID,
-        path = profiler_path.display()
+export CORECLR_PROFILER={guid}\n\
+export CORECLR_PROFILER_PATH={path}\n",
+        guid = quote::shell(&profiler_manifest().guid),
+        path = quote::shell(&profiler_path.to_string_lossy())
     );
     let plasma_env_file = plasma_env_dir.join("uprooted.sh");
-    let _ = fs::write(&plasma_env_file, &plasma_script);
+    write_atomic(&plasma_env_file, &plasma_script)?;
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
@@ -339,31 +445,29 @@ export CORECLR_PROFILER_PATH='{path}'\n",
 
     // 5. ~/.profile fallback -- for non-systemd sessions (X11 login shells, etc.)
     let profile_path = PathBuf::from(&home).join(".profile");
-    let profile_content = fs::read_to_string(&profile_path).unwrap_or_default();
+    // Rewritten whole rather than appended to, so never start from a failed read
+    let profile_content = match fs::read_to_string(&profile_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read ~/.profile: {}", e)),
+    };
     if !profile_content.contains("DOTNET_ENABLE_PROFILING") {
         let block = format!(
             "\n# Uprooted CLR profiler (remove these lines to disable)\n\
 # .NET 10+ (DOTNET_ prefix)\n\
 export DOTNET_EnableDiagnostics=1\n\
 export DOTNET_ENABLE_PROFILING=1\n\
-export DOTNET_PROFILER='{guid}'\n\
-export DOTNET_PROFILER_PATH='{path}'\n\
+export DOTNET_PROFILER={guid}\n\
+export DOTNET_PROFILER_PATH={path}\n\
 export DOTNET_ReadyToRun=0\n\
 # Legacy (.NET 8/9)\n\
 export CORECLR_ENABLE_PROFILING=1\n\
-export CORECLR_PROFILER='{guid}'\n\
-export CORECLR_PROFILER_PATH='{path}'\n",
-            guid = PROFILER_GUID,
-            path = profiler_path.display()
+export CORECLR_PROFILER={guid}\n\
+export CORECLR_PROFILER_PATH={path}\n",
+            guid = quote::shell(&profiler_manifest().guid),
+            path = quote::shell(&profiler_path.to_string_lossy())
         );
-        let mut file = fs::OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&profile_path)
-            .map_err(|e| format!("Failed to append to ~/.profile: {}", e))?;
-        use std::io::Write;
-        file.write_all(block.as_bytes())
-            .map_err(|e| format!("Failed to write to ~/.profile: {}", e))?;
+        write_atomic(&profile_path, profile_content + &block)?;
     }
 
     Ok(())
@@ -389,69 +493,100 @@ pub fn remove_env_vars() -> Result<(), String> {
 
     // Remove .desktop file
     let desktop_file = PathBuf::from(&home)
-        .join(".local/share/applications/root-uprooted.desktop");
+        .join(".local/share/applications")
+        .join(DESKTOP_FILE_ID);
     let _ = fs::remove_file(&desktop_file);
 
+    // Every step below is best-effort: one that fails mustn't leave the others undone
+    let mut errors = Vec::new();
+
+    // Hand root:// links back to Root's own handler
+    if let Err(e) = unregister_url_handler() {
+        errors.push(e);
+    }
+
+    // Remove our autostart entry and re-enable Root's own
+    if let Err(e) = set_autostart(false) {
+        errors.push(e);
+    }
+
+    // Remove icon installed for the .desktop file
+    remove_root_icon();
+
     // Remove env vars from ~/.profile if present
     let profile_path = PathBuf::from(&home).join(".profile");
-    if let Ok(content) = fs::read_to_string(&profile_path) {
-        if content.contains("DOTNET_ENABLE_PROFILING") || content.contains("CORECLR_ENABLE_PROFILING") {
-            // Remove the Uprooted block (comment + 4 export lines + blank line before)
-            let cleaned: Vec<&str> = content
-                .lines()
-                .collect::<Vec<_>>()
-                .into_iter()
-                .scan(false, |in_block, line| {
-                    if line.contains("# Uprooted CLR profiler") {
-                        *in_block = true;
-                        Some(None) // skip this line
-                    } else if *in_block && (line.starts_with("export CORECLR_")
-                        || line.starts_with("export DOTNET_")
-                        || line.starts_with('#') // skip intermediate comment lines within the block
-                        || line.is_empty())
-                    {
-                        // An empty line signals the end of our block (the block has no trailing
-                        // blank line, so an empty line here is the next content's separator)
-                        if line.is_empty() {
-                            *in_block = false;
-                        }
-                        Some(None) // skip
-                    } else {
+    if let Ok(content) = fs::read_to_string(&profile_path)
+        && (content.contains("DOTNET_ENABLE_PROFILING") || content.contains("CORECLR_ENABLE_PROFILING"))
+    {
+        // Remove the Uprooted block (comment + 4 export lines + blank line before)
+        let cleaned: Vec<&str> = content
+            .lines()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .scan(false, |in_block, line| {
+                if line.contains("# Uprooted CLR profiler") {
+                    *in_block = true;
+                    Some(None) // skip this line
+                } else if *in_block && (line.starts_with("export CORECLR_")
+                    || line.starts_with("export DOTNET_")
+                    || line.starts_with('#') // skip intermediate comment lines within the block
+                    || line.is_empty())
+                {
+                    // An empty line signals the end of our block (the block has no trailing
+                    // blank line, so an empty line here is the next content's separator)
+                    if line.is_empty() {
                         *in_block = false;
-                        Some(Some(line))
                     }
-                })
-                .flatten()
-                .collect();
-            let _ = fs::write(&profile_path, cleaned.join("\n") + "\n");
+                    Some(None) // skip
+                } else {
+                    *in_block = false;
+                    Some(Some(line))
+                }
+            })
+            .flatten()
+            .collect();
+        if let Err(e) = write_atomic(&profile_path, cleaned.join("\n") + "\n") {
+            errors.push(e);
         }
     }
 
-    Ok(())
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("; ")) }
 }
 
+/// Desktop file ID of the "Root (Uprooted)" entry.
+#[cfg(target_os = "linux")]
+const DESKTOP_FILE_ID: &str = "root-uprooted.desktop";
+
+/// MIME type for Root's `root://` URL scheme.
+#[cfg(target_os = "linux")]
+const URL_SCHEME_MIME: &str = "x-scheme-handler/root";
+
 /// Create a .desktop file that launches Root through the wrapper script.
 #[cfg(target_os = "linux")]
-fn create_desktop_file(wrapper: &PathBuf) -> Result<(), String> {
+fn create_desktop_file(wrapper: &Path, icon: Option<&str>) -> Result<(), String> {
     let home = std::env::var("HOME").unwrap_or_default();
     let apps_dir = PathBuf::from(&home).join(".local/share/applications");
     fs::create_dir_all(&apps_dir)
         .map_err(|e| format!("Failed to create applications dir: {}", e))?;
 
-    let desktop_content = format!(
+    let mut desktop_content = format!(
         "[Desktop Entry]\n\
 Name=Root (Uprooted)\n\
 Comment=Root Communications with Uprooted mods\n\
-Exec={}\n\
+Exec={} %u\n\
 Type=Application\n\
 Categories=Network;Chat;\n\
+MimeType={mime};\n\
 Terminal=false\n",
-        wrapper.display()
+        quote::desktop_exec(&wrapper.to_string_lossy()),
+        mime = URL_SCHEME_MIME,
     );
+    if let Some(icon) = icon {
+        desktop_content.push_str(&format!("Icon={}\n", icon));
+    }
 
-    let desktop_file = apps_dir.join("root-uprooted.desktop");
-    fs::write(&desktop_file, &desktop_content)
-        .map_err(|e| format!("Failed to write .desktop file: {}", e))?;
+    let desktop_file = apps_dir.join(DESKTOP_FILE_ID);
+    write_atomic(&desktop_file, &desktop_content)?;
 
     // chmod +x on desktop file
     #[cfg(unix)]
@@ -461,12 +596,509 @@ Terminal=false\n",
         let _ = std::fs::set_permissions(&desktop_file, perms);
     }
 
+    // Refresh the MIME cache so the MimeType= line is picked up (best-effort)
+    let _ = std::process::Command::new("update-desktop-database")
+        .arg(&apps_dir)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+
     Ok(())
 }
 
+// ==================== Linux: root:// URL handler ====================
+
+#[cfg(target_os = "linux")]
+fn mimeapps_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".config/mimeapps.list")
+}
+
+/// Make the Uprooted desktop entry the default `root://` handler in `~/.config/mimeapps.list`.
+///
+/// Our entry is put first in the list rather than replacing it, so whatever handler Root
+/// registered stays behind it and takes over again once we're unregistered.
+#[cfg(target_os = "linux")]
+fn register_url_handler() -> Result<(), String> {
+    let path = mimeapps_path();
+    let content = with_url_handler(&read_mimeapps(&path)?.unwrap_or_default());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    write_atomic(&path, content)
+}
+
+/// Remove the Uprooted desktop entry from the `root://` associations in `~/.config/mimeapps.list`.
+#[cfg(target_os = "linux")]
+fn unregister_url_handler() -> Result<(), String> {
+    let path = mimeapps_path();
+    let Some(original) = read_mimeapps(&path)? else {
+        return Ok(());
+    };
+    let content = without_url_handler(&original);
+    if content == original {
+        return Ok(());
+    }
+    write_atomic(&path, content)
+}
+
+/// The user's mimeapps.list, `None` when there is none. It is rewritten whole, so any other
+/// read failure is an error rather than an empty file.
+#[cfg(target_os = "linux")]
+fn read_mimeapps(path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// A mimeapps.list with our entry first for `root://` in both association sections.
+#[cfg(target_os = "linux")]
+fn with_url_handler(content: &str) -> String {
+    let mut content = content.to_string();
+    for section in ["Default Applications", "Added Associations"] {
+        content = edit_mimeapps_entry(&content, section, URL_SCHEME_MIME, |ids| {
+            ids.retain(|id| id != DESKTOP_FILE_ID);
+            ids.insert(0, DESKTOP_FILE_ID.to_string());
+        });
+    }
+    content
+}
+
+/// A mimeapps.list with our entry taken out of the `root://` associations.
+#[cfg(target_os = "linux")]
+fn without_url_handler(content: &str) -> String {
+    let mut content = content.to_string();
+    for section in ["Default Applications", "Added Associations"] {
+        content = edit_mimeapps_entry(&content, section, URL_SCHEME_MIME, |ids| {
+            ids.retain(|id| id != DESKTOP_FILE_ID);
+        });
+    }
+    content
+}
+
+/// Check whether `root://` links currently open the Uprooted desktop entry.
+///
+/// Asks `xdg-mime` (which also honours desktop-specific and system lists) and falls back
+/// to the first entry in the user's `mimeapps.list` when it isn't installed.
+#[cfg(target_os = "linux")]
+fn check_url_handler() -> bool {
+    if let Ok(output) = std::process::Command::new("xdg-mime")
+        .args(["query", "default", URL_SCHEME_MIME])
+        .output()
+        && output.status.success()
+    {
+        return String::from_utf8_lossy(&output.stdout).trim() == DESKTOP_FILE_ID;
+    }
+
+    let content = fs::read_to_string(mimeapps_path()).unwrap_or_default();
+    let mut in_defaults = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_defaults = line == "[Default Applications]";
+        } else if in_defaults
+            && let Some((key, value)) = line.split_once('=')
+            && key.trim() == URL_SCHEME_MIME
+        {
+            return value.split(';').next().map(str::trim) == Some(DESKTOP_FILE_ID);
+        }
+    }
+    false
+}
+
+/// Rewrite the desktop ID list for `mime` in one `[section]` of a mimeapps.list, leaving every
+/// other line untouched. Empty lists remove the key; missing keys and sections are appended.
+#[cfg(target_os = "linux")]
+fn edit_mimeapps_entry(
+    content: &str,
+    section: &str,
+    mime: &str,
+    edit: impl FnOnce(&mut Vec<String>),
+) -> String {
+    let header = format!("[{}]", section);
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    let section_start = lines.iter().position(|l| l.trim() == header);
+    let (body_start, body_end) = match section_start {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|l| l.trim_start().starts_with('['))
+                .map(|i| start + 1 + i)
+                .unwrap_or(lines.len());
+            (start + 1, end)
+        }
+        None => (lines.len(), lines.len()),
+    };
+
+    let existing = (body_start..body_end).find(|&i| {
+        lines[i]
+            .split_once('=')
+            .is_some_and(|(key, _)| key.trim() == mime)
+    });
+    let mut ids: Vec<String> = existing
+        .and_then(|i| lines[i].split_once('=').map(|(_, v)| v.to_string()))
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect();
+    edit(&mut ids);
+
+    let new_line = (!ids.is_empty()).then(|| format!("{}={};", mime, ids.join(";")));
+    match (existing, new_line) {
+        (Some(i), Some(line)) => lines[i] = line,
+        (Some(i), None) => {
+            lines.remove(i);
+        }
+        (None, Some(line)) => {
+            if section_start.is_none() {
+                if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(header);
+                lines.push(line);
+            } else {
+                // Insert after the section's last non-blank line to keep its trailing spacing
+                let insert_at = (body_start..body_end)
+                    .rev()
+                    .find(|&i| !lines[i].trim().is_empty())
+                    .map(|i| i + 1)
+                    .unwrap_or(body_start);
+                lines.insert(insert_at, line);
+            }
+        }
+        (None, None) => {}
+    }
+
+    let mut result = lines.join("\n");
+    if !result.is_empty() {
+        result.push('\n');
+    }
+    result
+}
+
+// ==================== Linux: XDG autostart ====================
+
+/// Suffix given to Root's own autostart entries while ours replaces them.
+#[cfg(target_os = "linux")]
+const AUTOSTART_DISABLED_SUFFIX: &str = ".uprooted-disabled";
+
+#[cfg(target_os = "linux")]
+fn autostart_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".config/autostart")
+}
+
+#[cfg(target_os = "linux")]
+fn autostart_entry_path() -> PathBuf {
+    autostart_dir().join(DESKTOP_FILE_ID)
+}
+
+/// Enable or disable launching Root through `launch-root.sh` at login.
+///
+/// Enabling writes `~/.config/autostart/root-uprooted.desktop` and moves Root's own
+/// autostart entries aside so Root isn't started twice (once unmodded). Disabling
+/// removes our entry and puts Root's back.
+#[cfg(target_os = "linux")]
+pub fn set_autostart(enabled: bool) -> Result<(), String> {
+    if !enabled {
+        return disable_autostart(&autostart_dir());
+    }
+    let wrapper = get_uprooted_dir().join("launch-root.sh");
+    if !wrapper.exists() {
+        return Err("launch-root.sh not found -- install Uprooted first".to_string());
+    }
+    enable_autostart(&autostart_dir(), &wrapper, &crate::detection::get_root_exe_path())
+}
+
+#[cfg(target_os = "linux")]
+fn enable_autostart(dir: &Path, wrapper: &Path, root_path: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    for path in find_root_autostart_entries(dir, root_path) {
+        let disabled = format!("{}{}", path.to_string_lossy(), AUTOSTART_DISABLED_SUFFIX);
+        fs::rename(&path, &disabled)
+            .map_err(|e| format!("Failed to disable {}: {}", path.display(), e))?;
+    }
+
+    let content = format!(
+        "[Desktop Entry]\n\
+Name=Root (Uprooted)\n\
+Comment=Start Root Communications with Uprooted mods at login\n\
+Exec={}\n\
+Type=Application\n\
+Terminal=false\n\
+X-GNOME-Autostart-enabled=true\n",
+        quote::desktop_exec(&wrapper.to_string_lossy())
+    );
+    write_atomic(&dir.join(DESKTOP_FILE_ID), content)
+}
+
+/// Remove our entry and put Root's back, going on past entries that can't be restored.
+/// A disabled entry whose original Root has since re-created is dropped, not restored
+/// over the new one.
+#[cfg(target_os = "linux")]
+fn disable_autostart(dir: &Path) -> Result<(), String> {
+    let _ = fs::remove_file(dir.join(DESKTOP_FILE_ID));
+    let mut errors = Vec::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(original) = name.strip_suffix(AUTOSTART_DISABLED_SUFFIX) else {
+                continue;
+            };
+            let restored = if dir.join(original).exists() {
+                fs::remove_file(&path)
+            } else {
+                fs::rename(&path, dir.join(original))
+            };
+            if let Err(e) = restored {
+                errors.push(format!("Failed to restore {}: {}", original, e));
+            }
+        }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("; ")) }
+}
+
+/// Find Root's own autostart entries: those whose `Exec` runs the Root binary or named "Root".
+#[cfg(target_os = "linux")]
+fn find_root_autostart_entries(dir: &Path, root_path: &Path) -> Vec<PathBuf> {
+    let root_str = root_path.to_string_lossy().to_string();
+    let mut found = Vec::new();
+
+    let Ok(entries) = fs::read_dir(dir) else { return found };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name() == DESKTOP_FILE_ID
+            || path.extension().and_then(|e| e.to_str()) != Some("desktop")
+        {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&path) else { continue };
+        let is_root = content.lines().any(|l| {
+            if let Some(exec) = l.strip_prefix("Exec=") {
+                let program = exec.trim().trim_start_matches('"');
+                !exec.contains("launch-root.sh") && program.starts_with(&root_str)
+            } else if let Some(name) = l.strip_prefix("Name=") {
+                name.trim().eq_ignore_ascii_case("Root")
+            } else {
+                false
+            }
+        });
+        if is_root {
+            found.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+/// Autostart is managed through XDG autostart entries, which only exist on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn set_autostart(_enabled: bool) -> Result<(), String> {
+    Err("Autostart is only supported on Linux".to_string())
+}
+
+// ==================== Linux: application icon ====================
+
+/// Theme icon name used for the extracted Root icon.
+#[cfg(target_os = "linux")]
+const ICON_NAME: &str = "root-uprooted";
+
+/// Extract Root's icon and install it into the user's hicolor icon theme.
+///
+/// Looks in an already-extracted `squashfs-root` first (either the one Root runs from
+/// or one next to the AppImage), then falls back to `--appimage-extract` into a private
+/// dir under the uprooted dir (type 2 AppImages only).
+/// Returns the theme icon name to use for `Icon=`, or `None` if no icon was found.
+#[cfg(target_os = "linux")]
+fn install_root_icon(root_path: &Path) -> Option<&'static str> {
+    // Already-extracted AppImage: Root runs from inside squashfs-root, or one sits next to it
+    let extracted = root_path
+        .ancestors()
+        .find(|p| p.file_name().is_some_and(|n| n == "squashfs-root"))
+        .map(Path::to_path_buf)
+        .or_else(|| root_path.parent().map(|p| p.join("squashfs-root")))
+        .filter(|p| p.is_dir());
+    if let Some(icon) = extracted.as_deref().and_then(find_appimage_icon) {
+        return install_icon_file(&icon).then_some(ICON_NAME);
+    }
+
+    // Only a type 2 runtime handles `--appimage-extract` itself; anything else would
+    // pass it on to Root and start it
+    if !is_type2_appimage(root_path) {
+        return None;
+    }
+
+    let tmp = get_uprooted_dir().join("icon-extract");
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir_all(&tmp).ok()?;
+    for pattern in [".DirIcon", "*.png", "*.svg"] {
+        let extract = std::process::Command::new(root_path)
+            .arg("--appimage-extract")
+            .arg(pattern)
+            .current_dir(&tmp)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        if extract.map_or(true, |child| !wait_with_timeout(child, ICON_EXTRACT_TIMEOUT)) {
+            break;
+        }
+    }
+
+    let installed = find_appimage_icon(&tmp.join("squashfs-root"))
+        .map(|icon| install_icon_file(&icon))
+        .unwrap_or(false);
+    let _ = fs::remove_dir_all(&tmp);
+    installed.then_some(ICON_NAME)
+}
+
+/// How long one `--appimage-extract` may run before it is killed.
+#[cfg(target_os = "linux")]
+const ICON_EXTRACT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Whether `path` is an ELF file with the type 2 AppImage magic (`AI\x02` at offset 8).
+#[cfg(target_os = "linux")]
+fn is_type2_appimage(path: &Path) -> bool {
+    use std::io::Read;
+    let mut header = [0u8; 11];
+    let read = fs::File::open(path).and_then(|mut file| file.read_exact(&mut header));
+    read.is_ok() && header.starts_with(b"\x7fELF") && &header[8..] == b"AI\x02"
+}
+
+/// Wait for `child` to exit, killing it after `timeout`. Returns whether it exited in time.
+#[cfg(target_os = "linux")]
+fn wait_with_timeout(mut child: std::process::Child, timeout: std::time::Duration) -> bool {
+    let deadline = std::time::Instant::now() + timeout;
+    while std::time::Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(Some(_)) => return true,
+            Ok(None) => std::thread::sleep(std::time::Duration::from_millis(50)),
+            Err(_) => break,
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    false
+}
+
+/// Pick the best icon from an extracted AppImage tree.
+///
+/// Prefers a scalable icon from `usr/share/icons`, then the largest PNG,
+/// then `.DirIcon` (usually a symlink to one of the top-level icons).
+#[cfg(target_os = "linux")]
+fn find_appimage_icon(squashfs_root: &Path) -> Option<PathBuf> {
+    let pattern = squashfs_root
+        .join("usr/share/icons/*/*/apps/*")
+        .to_string_lossy()
+        .to_string();
+    let mut best: Option<(u32, PathBuf)> = None;
+    for path in glob::glob(&pattern).ok()?.flatten() {
+        let Ok(data) = fs::read(&path) else { continue };
+        // Rank scalable above any raster size; prefer names that mention Root on ties
+        let size = match icon_size(&data) {
+            Some(IconSize::Scalable) => u32::MAX,
+            Some(IconSize::Pixels(px)) => px,
+            None => continue,
+        };
+        let named_root = path
+            .file_stem()
+            .is_some_and(|s| s.to_string_lossy().to_lowercase().contains("root"));
+        let better = match &best {
+            None => true,
+            Some((best_size, best_path)) => {
+                let best_named_root = best_path
+                    .file_stem()
+                    .is_some_and(|s| s.to_string_lossy().to_lowercase().contains("root"));
+                (size, named_root) > (*best_size, best_named_root)
+            }
+        };
+        if better {
+            best = Some((size, path));
+        }
+    }
+    if let Some((_, path)) = best {
+        return Some(path);
+    }
+
+    let dir_icon = squashfs_root.join(".DirIcon");
+    fs::read(&dir_icon)
+        .ok()
+        .filter(|data| icon_size(data).is_some())
+        .map(|_| dir_icon)
+}
+
+#[cfg(target_os = "linux")]
+enum IconSize {
+    Scalable,
+    Pixels(u32),
+}
+
+/// Identify an icon as SVG or PNG (reading the width from the IHDR chunk).
+#[cfg(target_os = "linux")]
+fn icon_size(data: &[u8]) -> Option<IconSize> {
+    const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if data.starts_with(PNG_MAGIC) && data.len() >= 24 && &data[12..16] == b"IHDR" {
+        let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+        return (width > 0).then_some(IconSize::Pixels(width));
+    }
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]).to_lowercase();
+    if head.contains("<svg") {
+        return Some(IconSize::Scalable);
+    }
+    None
+}
+
+/// Copy an icon into `~/.local/share/icons/hicolor/<size>/apps/` under [`ICON_NAME`].
+#[cfg(target_os = "linux")]
+fn install_icon_file(icon: &Path) -> bool {
+    let Ok(data) = fs::read(icon) else { return false };
+    let (size_dir, ext) = match icon_size(&data) {
+        Some(IconSize::Scalable) => ("scalable".to_string(), "svg"),
+        Some(IconSize::Pixels(px)) => (format!("{px}x{px}"), "png"),
+        None => return false,
+    };
+
+    // Drop icons from a previous install that may have had a different size
+    remove_root_icon();
+
+    let home = std::env::var("HOME").unwrap_or_default();
+    let apps_dir = PathBuf::from(&home)
+        .join(".local/share/icons/hicolor")
+        .join(size_dir)
+        .join("apps");
+    if fs::create_dir_all(&apps_dir).is_err() {
+        return false;
+    }
+    write_atomic(&apps_dir.join(format!("{ICON_NAME}.{ext}")), &data).is_ok()
+}
+
+/// Remove every installed copy of the Root icon from the user's hicolor theme.
+#[cfg(target_os = "linux")]
+fn remove_root_icon() {
+    let home = std::env::var("HOME").unwrap_or_default();
+    let pattern = PathBuf::from(&home)
+        .join(".local/share/icons/hicolor/*/apps")
+        .join(format!("{ICON_NAME}.*"))
+        .to_string_lossy()
+        .to_string();
+    if let Ok(paths) = glob::glob(&pattern) {
+        for path in paths.flatten() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
 /// Check env var status from wrapper script / ~/.zprofile on macOS.
 #[cfg(target_os = "macos")]
-fn check_env_vars() -> (bool, bool, bool, bool) {
+fn check_env_vars() -> (bool, Option<String>, bool, bool) {
     let home = std::env::var("HOME").unwrap_or_default();
     let dir = get_uprooted_dir();
     let content = fs::read_to_string(dir.join("launch-root.sh"))
@@ -476,7 +1108,7 @@ fn check_env_vars() -> (bool, bool, bool, bool) {
 
     let enable = content.contains("DOTNET_ENABLE_PROFILING=1")
         || content.contains("CORECLR_ENABLE_PROFILING=1");
-    let guid = content.contains(PROFILER_GUID);
+    let guid = parse_configured_guid(&content);
     let path = content.contains("DOTNET_PROFILER_PATH=")
         || content.contains("CORECLR_PROFILER_PATH=");
     let r2r = content.contains("DOTNET_ReadyToRun=0");
@@ -488,7 +1120,7 @@ fn check_env_vars() -> (bool, bool, bool, bool) {
 #[cfg(target_os = "macos")]
 pub fn set_env_vars() -> Result<(), String> {
     let dir = get_uprooted_dir();
-    let profiler_path = dir.join(PROFILER_FILENAME);
+    let profiler_path = dir.join(&profiler_manifest().filename);
     let root_path = crate::detection::get_root_exe_path();
     let home = std::env::var("HOME").unwrap_or_default();
 
@@ -499,19 +1131,18 @@ pub fn set_env_vars() -> Result<(), String> {
 # Uprooted launcher - sets CLR profiler env vars for Root only\n\
 export DOTNET_EnableDiagnostics=1\n\
 export DOTNET_ENABLE_PROFILING=1\n\
-export DOTNET_PROFILER='{guid}'\n\
-export DOTNET_PROFILER_PATH='{path}'\n\
+export DOTNET_PROFILER={guid}\n\
+export DOTNET_PROFILER_PATH={path}\n\
 export DOTNET_ReadyToRun=0\n\
 export CORECLR_ENABLE_PROFILING=1\n\
-export CORECLR_PROFILER='{guid}'\n\
-export CORECLR_PROFILER_PATH='{path}'\n\
-exec '{root}' \"$@\"\n",
-        guid = PROFILER_GUID,
-        path = profiler_path.display(),
-        root = root_path.display()
+export CORECLR_PROFILER={guid}\n\
+export CORECLR_PROFILER_PATH={path}\n\
+exec {root} \"$@\"\n",
+        guid = quote::shell(&profiler_manifest().guid),
+        path = quote::shell(&profiler_path.to_string_lossy()),
+        root = quote::shell(&root_path.to_string_lossy())
     );
-    fs::write(&wrapper, &script)
-        .map_err(|e| format!("Failed to write wrapper script: {}", e))?;
+    write_atomic(&wrapper, &script)?;
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
@@ -520,29 +1151,26 @@ exec '{root}' \"$@\"\n",
 
     // 2. ~/.zprofile fallback (macOS default shell is zsh)
     let zprofile = PathBuf::from(&home).join(".zprofile");
-    let content = fs::read_to_string(&zprofile).unwrap_or_default();
+    let content = match fs::read_to_string(&zprofile) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read ~/.zprofile: {}", e)),
+    };
     if !content.contains("DOTNET_ENABLE_PROFILING") {
         let block = format!(
             "\n# Uprooted CLR profiler (remove these lines to disable)\n\
 export DOTNET_EnableDiagnostics=1\n\
 export DOTNET_ENABLE_PROFILING=1\n\
-export DOTNET_PROFILER='{guid}'\n\
-export DOTNET_PROFILER_PATH='{path}'\n\
+export DOTNET_PROFILER={guid}\n\
+export DOTNET_PROFILER_PATH={path}\n\
 export DOTNET_ReadyToRun=0\n\
 export CORECLR_ENABLE_PROFILING=1\n\
-export CORECLR_PROFILER='{guid}'\n\
-export CORECLR_PROFILER_PATH='{path}'\n",
-            guid = PROFILER_GUID,
-            path = profiler_path.display()
+export CORECLR_PROFILER={guid}\n\
+export CORECLR_PROFILER_PATH={path}\n",
+            guid = quote::shell(&profiler_manifest().guid),
+            path = quote::shell(&profiler_path.to_string_lossy())
         );
-        use std::io::Write;
-        let mut file = fs::OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(&zprofile)
-            .map_err(|e| format!("Failed to append to ~/.zprofile: {}", e))?;
-        file.write_all(block.as_bytes())
-            .map_err(|e| format!("Failed to write to ~/.zprofile: {}", e))?;
+        write_atomic(&zprofile, content + &block)?;
     }
 
     Ok(())
@@ -559,32 +1187,32 @@ pub fn remove_env_vars() -> Result<(), String> {
 
     // Clean ~/.zprofile
     let zprofile = PathBuf::from(&home).join(".zprofile");
-    if let Ok(content) = fs::read_to_string(&zprofile) {
-        if content.contains("DOTNET_ENABLE_PROFILING") || content.contains("CORECLR_ENABLE_PROFILING") {
-            let cleaned: Vec<&str> = content
-                .lines()
-                .collect::<Vec<_>>()
-                .into_iter()
-                .scan(false, |in_block, line| {
-                    if line.contains("# Uprooted CLR profiler") {
-                        *in_block = true;
-                        Some(None)
-                    } else if *in_block && (line.starts_with("export CORECLR_")
-                        || line.starts_with("export DOTNET_")
-                        || line.starts_with('#')
-                        || line.is_empty())
-                    {
-                        if line.is_empty() { *in_block = false; }
-                        Some(None)
-                    } else {
-                        *in_block = false;
-                        Some(Some(line))
-                    }
-                })
-                .flatten()
-                .collect();
-            let _ = fs::write(&zprofile, cleaned.join("\n") + "\n");
-        }
+    if let Ok(content) = fs::read_to_string(&zprofile)
+        && (content.contains("DOTNET_ENABLE_PROFILING") || content.contains("CORECLR_ENABLE_PROFILING"))
+    {
+        let cleaned: Vec<&str> = content
+            .lines()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .scan(false, |in_block, line| {
+                if line.contains("# Uprooted CLR profiler") {
+                    *in_block = true;
+                    Some(None)
+                } else if *in_block && (line.starts_with("export CORECLR_")
+                    || line.starts_with("export DOTNET_")
+                    || line.starts_with('#')
+                    || line.is_empty())
+                {
+                    if line.is_empty() { *in_block = false; }
+                    Some(None)
+                } else {
+                    *in_block = false;
+                    Some(Some(line))
+                }
+            })
+            .flatten()
+            .collect();
+        write_atomic(&zprofile, cleaned.join("\n") + "\n")?;
     }
 
     Ok(())
@@ -598,7 +1226,7 @@ fn check_env_vars_active() -> bool {
         .unwrap_or(false);
     let guid = std::env::var("DOTNET_PROFILER")
         .or_else(|_| std::env::var("CORECLR_PROFILER"))
-        .map(|v| v == PROFILER_GUID)
+        .map(|v| is_current_guid(&v))
         .unwrap_or(false);
     let path = std::env::var("DOTNET_PROFILER_PATH")
         .or_else(|_| std::env::var("CORECLR_PROFILER_PATH"))
@@ -609,7 +1237,7 @@ fn check_env_vars_active() -> bool {
 
 /// Check env var status from environment.d config (falls back to wrapper script).
 #[cfg(target_os = "linux")]
-fn check_env_vars() -> (bool, bool, bool, bool) {
+fn check_env_vars() -> (bool, Option<String>, bool, bool) {
     let home = std::env::var("HOME").unwrap_or_default();
 
     // Check environment.d first (primary mechanism)
@@ -628,7 +1256,7 @@ fn check_env_vars() -> (bool, bool, bool, bool) {
 
     let enable = content.contains("DOTNET_ENABLE_PROFILING=1")
         || content.contains("CORECLR_ENABLE_PROFILING=1");
-    let guid = content.contains(PROFILER_GUID);
+    let guid = parse_configured_guid(&content);
     let path = content.contains("DOTNET_PROFILER_PATH=")
         || content.contains("CORECLR_PROFILER_PATH=");
     let r2r = content.contains("DOTNET_ReadyToRun=0");
@@ -647,7 +1275,7 @@ fn check_env_vars_active() -> bool {
     // On Windows, if the config (registry) says env vars are set, they'll be
     // active for any newly launched process. Return true if configured.
     let (enable, guid, path, _) = check_env_vars();
-    enable && guid && path
+    enable && guid.as_deref().is_some_and(is_current_guid) && path
 }
 
 #[cfg(target_os = "linux")]
@@ -658,7 +1286,7 @@ fn check_env_vars_active() -> bool {
         .unwrap_or(false);
     let guid = std::env::var("DOTNET_PROFILER")
         .or_else(|_| std::env::var("CORECLR_PROFILER"))
-        .map(|v| v == PROFILER_GUID)
+        .map(|v| is_current_guid(&v))
         .unwrap_or(false);
     let path = std::env::var("DOTNET_PROFILER_PATH")
         .or_else(|_| std::env::var("CORECLR_PROFILER_PATH"))
@@ -669,18 +1297,52 @@ fn check_env_vars_active() -> bool {
 
 // ==================== Common: file operations ====================
 
-/// Delete Uprooted settings files from the profile directory.
-/// Removes both uprooted-settings.ini (C# hook) and uprooted-settings.json (TypeScript),
-/// plus the message log file. This resets all plugin states, themes, and preferences.
-pub fn reset_settings() -> Result<u32, String> {
+/// User data in the profile directory that repair can reset (uninstall removes all of it).
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum UserData {
+    /// uprooted-settings.ini (C# hook) and uprooted-settings.json (TypeScript): plugin
+    /// states, themes and preferences
+    Settings,
+    /// uprooted-message-log.dat
+    MessageLog,
+    /// Both
+    All,
+}
+
+impl UserData {
+    fn files(self) -> &'static [&'static str] {
+        match self {
+            UserData::Settings => &["uprooted-settings.ini", "uprooted-settings.json"],
+            UserData::MessageLog => &["uprooted-message-log.dat"],
+            UserData::All => &["uprooted-settings.ini", "uprooted-settings.json", "uprooted-message-log.dat"],
+        }
+    }
+
+    /// Whether resetting `what` loses settings (rather than just the message log).
+    pub fn includes_settings(what: &[UserData]) -> bool {
+        what.iter().any(|w| *w != UserData::MessageLog)
+    }
+
+    /// "Settings", "Message log" or both, for progress messages.
+    pub fn describe(what: &[UserData]) -> &'static str {
+        match (Self::includes_settings(what), what.iter().any(|w| *w != UserData::Settings)) {
+            (true, true) => "Settings and message log",
+            (true, false) => "Settings",
+            _ => "Message log",
+        }
+    }
+}
+
+/// Delete the files making up `what` from the profile directory, including settings files
+/// set aside as invalid and saved settings profiles when settings are reset. Returns how
+/// many existed.
+pub fn reset_settings(what: &[UserData]) -> Result<u32, String> {
     let profile = crate::detection::get_profile_dir();
-    let files = [
-        "uprooted-settings.ini",
-        "uprooted-settings.json",
-        "uprooted-message-log.dat",
-    ];
+    let mut files: Vec<&str> = what.iter().flat_map(|w| w.files().iter().copied()).collect();
+    files.sort_unstable();
+    files.dedup();
     let mut deleted = 0u32;
-    for name in &files {
+    for name in files {
         let path = profile.join(name);
         if path.exists() {
             fs::remove_file(&path)
@@ -688,6 +1350,20 @@ pub fn reset_settings() -> Result<u32, String> {
             deleted += 1;
         }
     }
+    // Unusable settings files set aside by an edit, and `uprooted profile save` copies
+    if UserData::includes_settings(what) {
+        for path in crate::settings::quarantined_files() {
+            fs::remove_file(&path)
+                .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+            deleted += 1;
+        }
+        let profiles = crate::profiles::profiles_dir();
+        if profiles.exists() {
+            deleted += crate::profiles::list().len() as u32;
+            fs::remove_dir_all(&profiles)
+                .map_err(|e| format!("Failed to delete {}: {}", profiles.display(), e))?;
+        }
+    }
     Ok(deleted)
 }
 
@@ -705,19 +1381,28 @@ pub fn remove_files() -> Result<(), String> {
 pub fn check_hook_status() -> HookStatus {
     let dir = get_uprooted_dir();
 
-    let profiler_dll = dir.join(PROFILER_FILENAME).exists();
+    let profiler_dll = dir.join(&profiler_manifest().filename).exists();
     let hook_dll = dir.join("UprootedHook.dll").exists();
     let hook_deps = dir.join("UprootedHook.deps.json").exists();
     let preload_js = dir.join("uprooted-preload.js").exists();
     let theme_css = dir.join("uprooted.css").exists();
 
-    let (env_enable, env_guid, env_path, env_r2r) = check_env_vars();
+    let (env_enable, configured_guid, env_path, env_r2r) = check_env_vars();
+    let env_guid = configured_guid.as_deref().is_some_and(is_current_guid);
+    let env_outdated = configured_guid
+        .as_deref()
+        .is_some_and(|g| is_outdated_guid(g, &profiler_manifest().guid));
 
     let files_ok = profiler_dll && hook_dll && hook_deps && preload_js && theme_css;
     let env_ok = env_enable && env_guid && env_path;
 
     let env_vars_active = check_env_vars_active();
 
+    #[cfg(target_os = "linux")]
+    let (url_handler, autostart) = (check_url_handler(), autostart_entry_path().exists());
+    #[cfg(not(target_os = "linux"))]
+    let (url_handler, autostart) = (false, false);
+
     HookStatus {
         profiler_dll,
         hook_dll,
@@ -726,11 +1411,14 @@ pub fn check_hook_status() -> HookStatus {
         theme_css,
         env_enable_profiling: env_enable,
         env_profiler_guid: env_guid,
+        env_profiler_outdated: env_outdated,
         env_profiler_path: env_path,
         env_ready_to_run: env_r2r,
         files_ok,
         env_ok,
         env_vars_active,
+        url_handler,
+        autostart,
     }
 }
 
@@ -828,3 +1516,197 @@ fn find_root_pids() -> Vec<u32> {
     }
     pids
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory, removed again when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("uprooted-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn profiler_manifest_override_is_checked() {
+        let tmp = TempDir::new("profiler-manifest");
+        let write = |name: &str, json: &str| {
+            let path = tmp.0.join(name);
+            fs::write(&path, json).unwrap();
+            path
+        };
+
+        let good = write("good.json", r#"{"guid": "{0A1B2C3D-0000-4000-8000-00000000ABCD}", "filename": "libtest.so"}"#);
+        let manifest = load_profiler_manifest(&good).unwrap();
+        assert_eq!((manifest.guid.as_str(), manifest.filename.as_str()), ("{0A1B2C3D-0000-4000-8000-00000000ABCD}", "libtest.so"));
+        assert!(manifest.path.is_none());
+
+        let bad_guid = write("bad-guid.json", r#"{"guid": "0A1B2C3D-0000-4000-8000-00000000ABCD", "filename": "libtest.so"}"#);
+        let bad_name = write("bad-name.json", r#"{"guid": "{0A1B2C3D-0000-4000-8000-00000000ABCD}", "filename": "../libtest.so"}"#);
+        for path in [&bad_guid, &bad_name, &tmp.0.join("missing.json")] {
+            assert!(load_profiler_manifest(path).is_err(), "{}", path.display());
+        }
+
+        // A bad override falls back to the built-in manifest and says why
+        let (manifest, warning) = resolve_profiler_manifest(bad_guid.to_str());
+        assert_eq!(manifest.guid, env!("UPROOTED_PROFILER_GUID"));
+        assert!(warning.unwrap().starts_with("Ignoring UPROOTED_PROFILER_MANIFEST"));
+        assert!(resolve_profiler_manifest(good.to_str()).1.is_none());
+        assert!(resolve_profiler_manifest(None).1.is_none());
+    }
+
+    #[test]
+    fn stale_guids_are_outdated() {
+        let current = "{D1A6F5A0-1234-4567-89AB-CDEF01234567}";
+        assert!(is_valid_guid(current));
+        for guid in ["", "{}", "D1A6F5A0-1234-4567-89AB-CDEF01234567", "{D1A6F5A0-1234-4567-89AB-CDEF0123456}", "{D1A6F5A0-1234-4567-89AB-CDEF0123456G}"] {
+            assert!(!is_valid_guid(guid), "{guid}");
+            assert!(!same_guid(guid, current) && !is_outdated_guid(guid, current), "{guid}");
+        }
+
+        assert!(same_guid("{d1a6f5a0-1234-4567-89ab-cdef01234567}", current));
+        assert!(!is_outdated_guid("{d1a6f5a0-1234-4567-89ab-cdef01234567}", current));
+        assert!(is_outdated_guid("{0A1B2C3D-0000-4000-8000-00000000ABCD}", current));
+
+        #[cfg(unix)]
+        assert_eq!(
+            parse_configured_guid("export DOTNET_ENABLE_PROFILING=1\nexport DOTNET_PROFILER='{0A1B2C3D-0000-4000-8000-00000000ABCD}'\n").as_deref(),
+            Some("{0A1B2C3D-0000-4000-8000-00000000ABCD}")
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn url_handler_goes_first_and_comes_back_out() {
+        // No [Default Applications] section: both sections are appended
+        let original = "[Added Associations]\ntext/html=firefox.desktop;\n";
+        let registered = with_url_handler(original);
+        assert_eq!(
+            registered,
+            "[Added Associations]\ntext/html=firefox.desktop;\nx-scheme-handler/root=root-uprooted.desktop;\n\n[Default Applications]\nx-scheme-handler/root=root-uprooted.desktop;\n"
+        );
+        assert_eq!(without_url_handler(&registered), "[Added Associations]\ntext/html=firefox.desktop;\n\n[Default Applications]\n");
+
+        // Root's own handler stays behind ours and takes over again
+        let original = "[Default Applications]\nx-scheme-handler/root=root.desktop;\ntext/html=firefox.desktop;\n\n[Added Associations]\nx-scheme-handler/root=root.desktop;\n";
+        let registered = with_url_handler(original);
+        assert!(registered.contains("[Default Applications]\nx-scheme-handler/root=root-uprooted.desktop;root.desktop;\ntext/html"));
+        assert_eq!(with_url_handler(&registered), registered);
+        assert_eq!(without_url_handler(&registered), original);
+        assert_eq!(without_url_handler(original), original);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unreadable_mimeapps_list_is_not_treated_as_empty() {
+        let tmp = TempDir::new("mimeapps");
+        let path = tmp.0.join("mimeapps.list");
+        assert_eq!(read_mimeapps(&path), Ok(None));
+        fs::write(&path, b"[Default Applications]\ntext/html=\xff\n").unwrap();
+        assert!(read_mimeapps(&path).unwrap_err().starts_with("Failed to read"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn autostart_replaces_roots_entries_and_puts_them_back() {
+        let tmp = TempDir::new("autostart");
+        let dir = tmp.0.join("autostart");
+        let root = Path::new("/opt/Root/Root.AppImage");
+        let entry = |name: &str, content: &str| fs::write(dir.join(name), content).unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        entry("root.desktop", "[Desktop Entry]\nName=Root\nExec=/usr/bin/root-launcher\n");
+        entry("root-tray.desktop", "[Desktop Entry]\nName=Root tray\nExec=\"/opt/Root/Root.AppImage\" --tray\n");
+        entry("other.desktop", "[Desktop Entry]\nName=Other\nExec=/usr/bin/other\n");
+        entry("notes.txt", "Name=Root\n");
+        assert_eq!(find_root_autostart_entries(&dir, root), [dir.join("root-tray.desktop"), dir.join("root.desktop")]);
+
+        enable_autostart(&dir, Path::new("/home/u/.local/share/uprooted/launch-root.sh"), root).unwrap();
+        assert!(dir.join("root.desktop.uprooted-disabled").exists() && !dir.join("root.desktop").exists());
+        assert!(dir.join(DESKTOP_FILE_ID).exists() && dir.join("other.desktop").exists());
+        assert!(find_root_autostart_entries(&dir, root).is_empty(), "our own entry isn't Root's");
+
+        // Root re-created one of its entries meanwhile: that one is kept
+        entry("root-tray.desktop", "[Desktop Entry]\nName=Root tray (new)\n");
+        disable_autostart(&dir).unwrap();
+        assert!(!dir.join(DESKTOP_FILE_ID).exists());
+        assert_eq!(fs::read_to_string(dir.join("root.desktop")).unwrap(), "[Desktop Entry]\nName=Root\nExec=/usr/bin/root-launcher\n");
+        assert_eq!(fs::read_to_string(dir.join("root-tray.desktop")).unwrap(), "[Desktop Entry]\nName=Root tray (new)\n");
+        assert!(!dir.join("root-tray.desktop.uprooted-disabled").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    fn png(width: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        data.extend(width.to_be_bytes());
+        data.extend(width.to_be_bytes());
+        data
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn icon_size_reads_png_width_and_svg() {
+        assert!(matches!(icon_size(&png(256)), Some(IconSize::Pixels(256))));
+        assert!(matches!(icon_size(b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), Some(IconSize::Scalable)));
+        assert!(icon_size(&png(0)).is_none());
+        assert!(icon_size(b"\x89PNG\r\n").is_none());
+        assert!(icon_size(b"not an icon").is_none());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn best_icon_is_picked_from_an_extracted_tree() {
+        let tmp = TempDir::new("icon-tree");
+        let root = tmp.0.join("squashfs-root");
+        let icon = |rel: &str, data: &[u8]| {
+            let path = root.join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, data).unwrap();
+            path
+        };
+        assert_eq!(find_appimage_icon(&root), None);
+
+        let dir_icon = icon(".DirIcon", &png(64));
+        assert_eq!(find_appimage_icon(&root), Some(dir_icon));
+
+        // Size decides; a name mentioning Root only breaks ties
+        icon("usr/share/icons/hicolor/48x48/apps/root.png", &png(48));
+        let large = icon("usr/share/icons/hicolor/512x512/apps/helper.png", &png(512));
+        icon("usr/share/icons/hicolor/32x32/apps/broken.png", b"garbage");
+        assert_eq!(find_appimage_icon(&root), Some(large));
+        let large_root = icon("usr/share/icons/other/512x512/apps/root.png", &png(512));
+        assert_eq!(find_appimage_icon(&root), Some(large_root));
+
+        let svg = icon("usr/share/icons/hicolor/scalable/apps/helper.svg", b"<svg/>");
+        assert_eq!(find_appimage_icon(&root), Some(svg));
+        let root_svg = icon("usr/share/icons/hicolor/scalable/apps/root.svg", b"<svg/>");
+        assert_eq!(find_appimage_icon(&root), Some(root_svg));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn only_type2_appimages_are_extracted() {
+        let tmp = TempDir::new("appimage-type");
+        let file = |name: &str, data: &[u8]| {
+            let path = tmp.0.join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        assert!(is_type2_appimage(&file("type2.AppImage", b"\x7fELF\x02\x01\x01\0AI\x02\0\0")));
+        assert!(!is_type2_appimage(&file("type1.AppImage", b"\x7fELF\x02\x01\x01\0AI\x01\0\0")));
+        assert!(!is_type2_appimage(&file("script.AppImage", b"#!/bin/sh\nexec root\n")));
+        assert!(!is_type2_appimage(&file("short.AppImage", b"\x7fELF")));
+        assert!(!is_type2_appimage(&tmp.0.join("missing.AppImage")));
+    }
+}
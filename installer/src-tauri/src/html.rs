@@ -0,0 +1,454 @@
+//! Minimal HTML tokenizer for finding where to inject into Root's bundle HTML.
+//!
+//! Only tracks what matters for picking a safe insertion point: comments, doctype,
+//! start/end tag names with quoted attributes, and raw-text elements (`<script>`,
+//! `<style>`, ...) whose contents must not be mistaken for markup. The same tokenizer
+//! exposes start tags and their attributes for the CSP checks in `csp`.
+
+use std::ops::Range;
+
+/// Where the injection goes, in order of preference.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InjectionKind {
+    /// Immediately before the first `</head>` that precedes `<body>`.
+    HeadEnd,
+    /// Immediately before the `<body>` start tag (still in the implied head).
+    BodyStart,
+    /// After the leading doctype / `<html>` / `<head>` start tags.
+    DocumentStart,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InjectionPoint {
+    /// Byte offset in the document to insert at.
+    pub offset: usize,
+    pub kind: InjectionKind,
+}
+
+/// Elements whose content is raw text (or escapable raw text) up to their end tag.
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+    "script", "style", "xmp", "iframe", "noembed", "noframes", "noscript", "title", "textarea",
+];
+
+/// A start or end tag, as found by [`start_tags`].
+#[derive(Clone, Debug)]
+pub struct Tag {
+    /// Lowercased tag name.
+    pub name: String,
+    pub is_end: bool,
+    /// Byte offset of the `<`.
+    pub start: usize,
+    /// Byte offset just past the `>`.
+    pub end: usize,
+}
+
+/// An attribute of a start tag.
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    /// Lowercased attribute name.
+    pub name: String,
+    /// Value with character references decoded; `None` for a bare attribute.
+    pub value: Option<String>,
+    /// Byte range of the whole attribute, name through value.
+    pub span: Range<usize>,
+    /// Byte range of the raw value, including its quotes.
+    pub value_span: Option<Range<usize>>,
+}
+
+enum Token {
+    Doctype { end: usize },
+    Tag(Tag),
+}
+
+/// Yields doctypes and tags in document order, skipping comments and raw text.
+///
+/// Stops at the first unterminated construct, recording it in `unterminated`.
+struct Tokenizer<'a> {
+    html: &'a str,
+    pos: usize,
+    done: bool,
+    unterminated: Option<String>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(html: &'a str) -> Self {
+        Tokenizer { html, pos: 0, done: false, unterminated: None }
+    }
+
+    fn stop(&mut self, what: String) -> Option<Token> {
+        self.unterminated = Some(what);
+        self.done = true;
+        None
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let html = self.html;
+        let bytes = html.as_bytes();
+        if self.done {
+            return None;
+        }
+
+        while let Some(rel) = html[self.pos..].find('<') {
+            let i = self.pos + rel;
+            let rest = &html[i..];
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                match comment.find("-->") {
+                    Some(end) => self.pos = i + 4 + end + 3,
+                    None => return self.stop(format!("comment at byte {}", i)),
+                }
+                continue;
+            }
+
+            if rest.starts_with("<!") || rest.starts_with("<?") {
+                // Doctype or bogus comment: runs to the next '>'
+                match rest.find('>') {
+                    Some(end) => {
+                        self.pos = i + end + 1;
+                        if starts_with_ignore_case(&rest[2..], "doctype") {
+                            return Some(Token::Doctype { end: self.pos });
+                        }
+                    }
+                    None => return self.stop(format!("markup declaration at byte {}", i)),
+                }
+                continue;
+            }
+
+            let is_end = rest.starts_with("</");
+            let name_start = i + if is_end { 2 } else { 1 };
+            if !bytes.get(name_start).is_some_and(u8::is_ascii_alphabetic) {
+                // A bare '<' in text
+                self.pos = i + 1;
+                continue;
+            }
+            let name_end = html[name_start..]
+                .find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+                .map(|n| name_start + n)
+                .unwrap_or(html.len());
+            let name = html[name_start..name_end].to_ascii_lowercase();
+
+            let Some(tag_end) = find_tag_end(html, name_end) else {
+                return self.stop(format!("<{}{}> tag at byte {}", if is_end { "/" } else { "" }, name, i));
+            };
+            self.pos = tag_end;
+
+            if !is_end && name == "plaintext" {
+                self.done = true;
+            } else if !is_end && RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+                match find_raw_text_end(html, tag_end, &name) {
+                    Some(end) => self.pos = end,
+                    None => {
+                        self.unterminated = Some(format!("<{}> element at byte {}", name, i));
+                        self.done = true;
+                    }
+                }
+            }
+            return Some(Token::Tag(Tag { name, is_end, start: i, end: tag_end }));
+        }
+
+        self.done = true;
+        None
+    }
+}
+
+/// Find the injection point in `html`, preferring `</head>`, then `<body>`, then document start.
+///
+/// Fails when none exists, e.g. the file isn't HTML or is truncated before any of them.
+pub fn find_injection_point(html: &str) -> Result<InjectionPoint, String> {
+    let mut tokens = Tokenizer::new(html);
+    let mut doc_start: Option<usize> = None;
+
+    for token in tokens.by_ref() {
+        let tag = match token {
+            Token::Doctype { end } => {
+                doc_start.get_or_insert(end);
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+        match (tag.is_end, tag.name.as_str()) {
+            (true, "head") => {
+                return Ok(InjectionPoint { offset: tag.start, kind: InjectionKind::HeadEnd });
+            }
+            (false, "body") => {
+                return Ok(InjectionPoint { offset: tag.start, kind: InjectionKind::BodyStart });
+            }
+            (false, "html") | (false, "head") => doc_start = Some(tag.end),
+            _ => {}
+        }
+    }
+
+    if let Some(offset) = doc_start {
+        return Ok(InjectionPoint { offset, kind: InjectionKind::DocumentStart });
+    }
+    Err(match tokens.unterminated {
+        Some(what) => format!("no </head>, <body> or <html> before unterminated {}", what),
+        None => "no </head>, <body>, <html> or doctype found (not an HTML document?)".to_string(),
+    })
+}
+
+/// Every start tag in `html`, in document order.
+pub fn start_tags(html: &str) -> impl Iterator<Item = Tag> + '_ {
+    Tokenizer::new(html).filter_map(|token| match token {
+        Token::Tag(tag) if !tag.is_end => Some(tag),
+        _ => None,
+    })
+}
+
+/// Text content of the raw-text element (`<script>`, `<style>`) opened by `tag`.
+pub fn raw_text<'a>(html: &'a str, tag: &Tag) -> Option<&'a str> {
+    find_raw_text_end(html, tag.end, &tag.name).map(|end| &html[tag.end..end])
+}
+
+/// Parse the attributes of a start tag.
+pub fn attributes(html: &str, tag: &Tag) -> Vec<Attribute> {
+    let bytes = html.as_bytes();
+    // Everything between the tag name and the closing '>'
+    let end = tag.end - 1;
+    let mut pos = tag.start + 1 + tag.name.len();
+    let mut attrs = Vec::new();
+
+    loop {
+        while pos < end && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'/') {
+            pos += 1;
+        }
+        if pos >= end {
+            break;
+        }
+
+        let name_start = pos;
+        pos += 1;
+        while pos < end && !bytes[pos].is_ascii_whitespace() && !matches!(bytes[pos], b'/' | b'=') {
+            pos += 1;
+        }
+        let name = html[name_start..pos].to_ascii_lowercase();
+
+        let mut after_name = pos;
+        while after_name < end && bytes[after_name].is_ascii_whitespace() {
+            after_name += 1;
+        }
+        if after_name >= end || bytes[after_name] != b'=' {
+            attrs.push(Attribute { name, value: None, span: name_start..pos, value_span: None });
+            continue;
+        }
+
+        pos = after_name + 1;
+        while pos < end && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let value_start = pos;
+        let raw = match bytes.get(pos) {
+            Some(&q) if (q == b'"' || q == b'\'') && pos < end => {
+                let close = html[pos + 1..end].find(q as char).map_or(end, |n| pos + 1 + n);
+                pos = (close + 1).min(end);
+                &html[value_start + 1..close]
+            }
+            _ => {
+                while pos < end && !bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                &html[value_start..pos]
+            }
+        };
+        attrs.push(Attribute {
+            name,
+            value: Some(decode_attribute(raw)),
+            span: name_start..pos,
+            value_span: Some(value_start..pos),
+        });
+    }
+    attrs
+}
+
+/// Escape text for a double-quoted attribute value; the inverse of [`decode_attribute`].
+pub fn escape_attribute(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;")
+}
+
+/// Decode the character references that turn up in attribute values.
+///
+/// Covers the XML entities and numeric references; anything else is kept literally.
+pub fn decode_attribute(raw: &str) -> String {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(semi) = rest.find(';').filter(|&n| n <= 10) else {
+            decoded.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let c = match entity {
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Find the end of a tag (just past `>`), skipping `>` inside quoted attribute values.
+fn find_tag_end(html: &str, from: usize) -> Option<usize> {
+    let mut quote: Option<u8> = None;
+    for (offset, &b) in html.as_bytes()[from..].iter().enumerate() {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"') | (None, b'\'') => quote = Some(b),
+            (None, b'>') => return Some(from + offset + 1),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// Find the start of the `</name` end tag closing a raw-text element.
+fn find_raw_text_end(html: &str, from: usize, name: &str) -> Option<usize> {
+    let bytes = html.as_bytes();
+    let mut pos = from;
+    while let Some(rel) = html[pos..].find("</") {
+        let i = pos + rel;
+        let after = i + 2 + name.len();
+        if starts_with_ignore_case(&html[i + 2..], name)
+            && bytes
+                .get(after)
+                .is_none_or(|&b| b.is_ascii_whitespace() || b == b'/' || b == b'>')
+        {
+            return Some(i);
+        }
+        pos = i + 2;
+    }
+    None
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len() && s.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEBRTC_BUNDLE: &str = include_str!("../tests/fixtures/webrtc-bundle.html");
+    const ROOT_APP: &str = include_str!("../tests/fixtures/root-app.html");
+
+    fn point(html: &str) -> InjectionPoint {
+        find_injection_point(html).unwrap_or_else(|e| panic!("no injection point: {e}"))
+    }
+
+    #[test]
+    fn webrtc_bundle_injects_before_real_head_end() {
+        let p = point(WEBRTC_BUNDLE);
+        assert_eq!(p.kind, InjectionKind::HeadEnd);
+        // The comment, inline script and style all mention </head>; the real one comes last
+        assert_eq!(p.offset, WEBRTC_BUNDLE.rfind("</head>").unwrap());
+        assert_eq!(WEBRTC_BUNDLE.matches("</head>").count(), 5);
+    }
+
+    #[test]
+    fn root_app_injects_before_head_end() {
+        let p = point(ROOT_APP);
+        assert_eq!(p.kind, InjectionKind::HeadEnd);
+        assert!(ROOT_APP[p.offset..].starts_with("</head>"));
+    }
+
+    #[test]
+    fn ignores_head_end_in_attributes_and_rcdata() {
+        let html = r#"<html><head><meta content="</head>"><title></head></title></HEAD><body></body>"#;
+        let p = point(html);
+        assert_eq!(p.kind, InjectionKind::HeadEnd);
+        assert_eq!(&html[p.offset..p.offset + 7], "</HEAD>");
+    }
+
+    #[test]
+    fn falls_back_to_body() {
+        let html = "<!doctype html>\n<html>\n<meta charset=\"utf-8\">\n<body class=\"app\">\n<div></div>\n</body>\n</html>\n";
+        let p = point(html);
+        assert_eq!(p.kind, InjectionKind::BodyStart);
+        assert!(html[p.offset..].starts_with("<body class"));
+    }
+
+    #[test]
+    fn head_end_after_body_is_not_used() {
+        let html = "<html><body><p>x</p></head></body></html>";
+        assert_eq!(point(html).kind, InjectionKind::BodyStart);
+    }
+
+    #[test]
+    fn falls_back_to_document_start() {
+        let html = "<!DOCTYPE html><html lang=\"en\"><head><script src=\"a.js\"></script>";
+        let p = point(html);
+        assert_eq!(p.kind, InjectionKind::DocumentStart);
+        assert_eq!(&html[..p.offset], "<!DOCTYPE html><html lang=\"en\"><head>");
+
+        let html = "<!doctype html>\n<div>no head or body</div>";
+        let p = point(html);
+        assert_eq!(p.kind, InjectionKind::DocumentStart);
+        assert_eq!(&html[..p.offset], "<!doctype html>");
+    }
+
+    #[test]
+    fn parses_attributes() {
+        let html = r#"<meta http-equiv=Content-Security-Policy content="default-src 'self'; script-src &apos;none&#39;" data-x='a"b' defer>"#;
+        let tag = start_tags(html).next().unwrap();
+        assert_eq!(tag.name, "meta");
+        let attrs = attributes(html, &tag);
+        let names: Vec<&str> = attrs.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["http-equiv", "content", "data-x", "defer"]);
+        assert_eq!(attrs[0].value.as_deref(), Some("Content-Security-Policy"));
+        assert_eq!(attrs[1].value.as_deref(), Some("default-src 'self'; script-src 'none'"));
+        assert!(html[attrs[1].value_span.clone().unwrap()].starts_with("\"default-src"));
+        assert_eq!(attrs[2].value.as_deref(), Some("a\"b"));
+        assert_eq!(&html[attrs[2].span.clone()], "data-x='a\"b'");
+        assert_eq!(attrs[3].value, None);
+    }
+
+    #[test]
+    fn start_tags_skip_comments_and_raw_text() {
+        let html = "<head><!-- <meta a> --><script>let s = '<meta b>';</script><meta c></head>";
+        let tags: Vec<Tag> = start_tags(html).collect();
+        let names: Vec<&str> = tags.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["head", "script", "meta"]);
+        assert_eq!(raw_text(html, &tags[1]), Some("let s = '<meta b>';"));
+        assert_eq!(&html[tags[2].start..tags[2].end], "<meta c>");
+    }
+
+    #[test]
+    fn errors_without_safe_point() {
+        assert!(find_injection_point("").is_err());
+        assert!(find_injection_point("{\"error\": \"not html\"}").is_err());
+        assert!(find_injection_point("<div>fragment</div>").is_err());
+
+        let err = find_injection_point("<script>document.write('</head>')").unwrap_err();
+        assert!(err.contains("unterminated <script>"), "{err}");
+
+        let err = find_injection_point("<!-- <head></head>").unwrap_err();
+        assert!(err.contains("unterminated comment"), "{err}");
+    }
+}
@@ -0,0 +1,131 @@
+//! Minimal INI documents, as written by the C# hook (`uprooted-settings.ini`).
+//!
+//! `[section]` headers and `key=value` lines; `;` and `#` start comments. Section and key
+//! names compare case-insensitively. Edits keep every other line (comments, ordering,
+//! keys the installer doesn't know about) and the file's line endings.
+
+pub struct Ini {
+    lines: Vec<String>,
+    line_ending: &'static str,
+    trailing_newline: bool,
+}
+
+enum Line<'a> {
+    Section(&'a str),
+    Entry(&'a str, &'a str),
+    Other,
+}
+
+fn parse_line(line: &str) -> Line<'_> {
+    let line = line.trim();
+    if line.starts_with(';') || line.starts_with('#') {
+        return Line::Other;
+    }
+    if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        return Line::Section(name.trim());
+    }
+    match line.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Line::Entry(key.trim(), value.trim()),
+        _ => Line::Other,
+    }
+}
+
+impl Ini {
+    pub fn parse(content: &str) -> Self {
+        Ini {
+            lines: content.lines().map(str::to_string).collect(),
+            line_ending: if content.contains("\r\n") { "\r\n" } else { "\n" },
+            trailing_newline: content.is_empty() || content.ends_with('\n'),
+        }
+    }
+
+    /// Every `(section, key, value)` in file order; keys before the first header are in `""`.
+    pub fn entries(&self) -> Vec<(&str, &str, &str)> {
+        let mut section = "";
+        let mut entries = Vec::new();
+        for line in &self.lines {
+            match parse_line(line) {
+                Line::Section(name) => section = name,
+                Line::Entry(key, value) => entries.push((section, key, value)),
+                Line::Other => {}
+            }
+        }
+        entries
+    }
+
+    /// Set `key` in `section`, in place if present, else at the end of the section
+    /// (which is appended if missing).
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let mut current = "";
+        let mut section_end = None;
+        for (i, line) in self.lines.iter().enumerate() {
+            match parse_line(line) {
+                Line::Section(name) => current = name,
+                Line::Entry(k, _) if current.eq_ignore_ascii_case(section) && k.eq_ignore_ascii_case(key) => {
+                    self.lines[i] = format!("{}={}", k, value);
+                    return;
+                }
+                _ => {}
+            }
+            if current.eq_ignore_ascii_case(section) && !line.trim().is_empty() {
+                section_end = Some(i + 1);
+            }
+        }
+        match section_end {
+            Some(end) => self.lines.insert(end, format!("{}={}", key, value)),
+            None => {
+                if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{}]", section));
+                self.lines.push(format!("{}={}", key, value));
+            }
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut content = self.lines.join(self.line_ending);
+        if self.trailing_newline && !content.is_empty() {
+            content.push_str(self.line_ending);
+        }
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get<'a>(ini: &'a Ini, section: &str, key: &str) -> Option<&'a str> {
+        ini.entries()
+            .into_iter()
+            .find(|(s, k, _)| s.eq_ignore_ascii_case(section) && k.eq_ignore_ascii_case(key))
+            .map(|(_, _, value)| value)
+    }
+
+    #[test]
+    fn edits_keep_everything_else() {
+        let content = "; written by UprootedHook\r\n[Uprooted]\r\nEnabled = True\r\n\r\n[Plugins]\r\nMessageLogger=true\r\n";
+        let mut ini = Ini::parse(content);
+        assert_eq!(get(&ini, "uprooted", "enabled"), Some("True"));
+        assert_eq!(get(&ini, "Plugins", "themes"), None);
+        assert_eq!(ini.render(), content);
+
+        ini.set("Uprooted", "enabled", "false");
+        ini.set("Plugins", "themes", "true");
+        ini.set("Plugin:themes", "theme", "crimson");
+        assert_eq!(
+            ini.render(),
+            "; written by UprootedHook\r\n[Uprooted]\r\nEnabled=false\r\n\r\n[Plugins]\r\nMessageLogger=true\r\nthemes=true\r\n\r\n[Plugin:themes]\r\ntheme=crimson\r\n"
+        );
+        assert_eq!(
+            ini.entries(),
+            [
+                ("Uprooted", "Enabled", "false"),
+                ("Plugins", "MessageLogger", "true"),
+                ("Plugins", "themes", "true"),
+                ("Plugin:themes", "theme", "crimson"),
+            ]
+        );
+    }
+}
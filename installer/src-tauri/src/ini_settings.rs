@@ -0,0 +1,279 @@
+//! The hook's `uprooted-settings.ini` next to the preload's `uprooted-settings.json`.
+//!
+//! Both live in the profile directory and cover overlapping settings, so each INI key the
+//! web side also has is mapped to a settings path, `<name>` being one of the web plugins in
+//! [`manifest::PLUGINS`]:
+//!
+//! | INI                             | JSON                          |
+//! |---------------------------------|-------------------------------|
+//! | `[Uprooted]` `Enabled`          | `enabled`                     |
+//! | `[Plugins]` `<name>`            | `plugins.<name>.enabled`      |
+//! | `[Plugin:<name>]` `<key>`       | `plugins.<name>.config.<key>` |
+//!
+//! The hook keeps its own plugins' switches (`MessageLogger`, `ClearURLs`, ...) in the same
+//! `[Plugins]` section. Those and every other INI key are native-only and left alone; JSON
+//! values an INI line can't hold (objects, arrays, null) are web-only.
+//!
+//! Neither the hook's settings store (in the UprootedHook project) nor an INI file it wrote
+//! is part of this tree, so the layout above is unconfirmed. Until it is, syncing into the
+//! INI file only rewrites keys the hook has already written and never adds sections or keys.
+
+use crate::detection::get_profile_dir;
+use crate::fsutil::write_atomic;
+use crate::ini::Ini;
+use crate::manifest;
+use crate::settings::UprootedSettings;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const PLUGIN_SECTION_PREFIX: &str = "Plugin:";
+
+/// Which file wins when synchronising.
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Source {
+    Ini,
+    Json,
+}
+
+/// A mapped setting whose two sides don't match; `None` where a file doesn't have it.
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    pub path: String,
+    pub ini: Option<Value>,
+    pub json: Option<Value>,
+}
+
+pub fn ini_path() -> PathBuf {
+    get_profile_dir().join("uprooted-settings.ini")
+}
+
+/// The hook's settings; empty when it hasn't written any.
+pub fn load_ini() -> Result<Ini, String> {
+    let path = ini_path();
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(Ini::parse(&content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Ini::parse("")),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+pub fn save_ini(ini: &Ini) -> Result<(), String> {
+    write_atomic(&ini_path(), ini.render())
+}
+
+/// Whether `name` is a web plugin; the hook's own plugins share the `[Plugins]` section.
+fn is_web_plugin(name: &str) -> bool {
+    manifest::find(name).is_some()
+}
+
+/// Settings path of an INI key, if the web side has it too.
+fn path_for(section: &str, key: &str) -> Option<String> {
+    if section.eq_ignore_ascii_case("Uprooted") && key.eq_ignore_ascii_case("Enabled") {
+        return Some("enabled".to_string());
+    }
+    if section.eq_ignore_ascii_case("Plugins") {
+        return is_web_plugin(key).then(|| format!("plugins.{}.enabled", key));
+    }
+    let plugin = section.get(..PLUGIN_SECTION_PREFIX.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(PLUGIN_SECTION_PREFIX))
+        .map(|_| &section[PLUGIN_SECTION_PREFIX.len()..])?;
+    (is_web_plugin(plugin) && !key.contains('.'))
+        .then(|| format!("plugins.{}.config.{}", plugin, key))
+}
+
+/// INI section and key for a settings path; the inverse of `path_for`.
+fn key_for(path: &str) -> Option<(String, String)> {
+    if path == "enabled" {
+        return Some(("Uprooted".to_string(), "Enabled".to_string()));
+    }
+    let (plugin, rest) = path.strip_prefix("plugins.")?.split_once('.')?;
+    if !is_web_plugin(plugin) {
+        return None;
+    }
+    match rest.split_once('.') {
+        None if rest == "enabled" => Some(("Plugins".to_string(), plugin.to_string())),
+        Some(("config", key)) if !key.contains('.') => {
+            Some((format!("{}{}", PLUGIN_SECTION_PREFIX, plugin), key.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// INI values are untyped: `true`/`false` (any case, as .NET writes `True`) and numbers
+/// are read as such, anything else as a string.
+fn parse_value(raw: &str) -> Value {
+    if raw.eq_ignore_ascii_case("true") || raw.eq_ignore_ascii_case("false") {
+        return Value::Bool(raw.eq_ignore_ascii_case("true"));
+    }
+    if let Ok(n) = raw.parse::<i64>() {
+        return Value::from(n);
+    }
+    if let Ok(n) = raw.parse::<f64>()
+        && n.is_finite()
+    {
+        return Value::from(n);
+    }
+    Value::String(raw.to_string())
+}
+
+fn format_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) if !s.contains(['\r', '\n']) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Mapped settings in the INI file, by settings path.
+fn ini_values(ini: &Ini) -> BTreeMap<String, Value> {
+    ini.entries()
+        .into_iter()
+        .filter_map(|(section, key, raw)| Some((path_for(section, key)?, parse_value(raw))))
+        .collect()
+}
+
+/// Settings in the JSON file an INI line can hold, by settings path.
+fn json_values(settings: &UprootedSettings) -> BTreeMap<String, Value> {
+    let mut values = BTreeMap::new();
+    values.insert("enabled".to_string(), Value::Bool(settings.enabled));
+    for (name, plugin) in &settings.plugins {
+        if !is_web_plugin(name) {
+            continue;
+        }
+        values.insert(format!("plugins.{}.enabled", name), Value::Bool(plugin.enabled));
+        for (key, value) in &plugin.config {
+            if !key.contains('.') && format_value(value).is_some() {
+                values.insert(format!("plugins.{}.config.{}", name, key), value.clone());
+            }
+        }
+    }
+    values
+}
+
+/// Mapped settings that differ between the two files, sorted by path.
+pub fn differences(ini: &Ini, settings: &UprootedSettings) -> Vec<Difference> {
+    let ini_values = ini_values(ini);
+    let json_values = json_values(settings);
+    let mut paths: Vec<&String> = ini_values.keys().chain(json_values.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter(|path| ini_values.get(*path) != json_values.get(*path))
+        .map(|path| Difference {
+            path: path.clone(),
+            ini: ini_values.get(path).cloned(),
+            json: json_values.get(path).cloned(),
+        })
+        .collect()
+}
+
+/// Copy every mapped INI setting into `settings`. Returns how many changed, and one line
+/// per value the JSON side can't take (e.g. `Enabled=yes`).
+pub fn sync_to_json(ini: &Ini, settings: &mut UprootedSettings) -> (usize, Vec<String>) {
+    let mut changed = 0;
+    let mut problems = Vec::new();
+    for difference in differences(ini, settings) {
+        let Some(value) = difference.ini else {
+            continue;
+        };
+        match settings.set(&difference.path, value) {
+            Ok(()) => changed += 1,
+            Err(e) => problems.push(e),
+        }
+    }
+    (changed, problems)
+}
+
+/// Copy every JSON setting an INI line can hold into the keys `ini` already has. Returns
+/// how many changed, and one line per setting left out because the key would be new.
+pub fn sync_to_ini(settings: &UprootedSettings, ini: &mut Ini) -> (usize, Vec<String>) {
+    let mut changed = 0;
+    let mut skipped = Vec::new();
+    for difference in differences(ini, settings) {
+        let (Some(value), Some((section, key))) = (difference.json, key_for(&difference.path)) else {
+            continue;
+        };
+        let Some(raw) = format_value(&value) else {
+            continue;
+        };
+        if difference.ini.is_none() {
+            skipped.push(format!("{} (no [{}] {} key in the INI file to update)", difference.path, section, key));
+            continue;
+        }
+        ini.set(&section, &key, &raw);
+        changed += 1;
+    }
+    (changed, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> UprootedSettings {
+        serde_json::from_str(
+            r#"{
+                "enabled": true,
+                "plugins": {
+                    "themes": {"enabled": true, "config": {"theme": "crimson", "stops": [1, 2]}},
+                    "link-embeds": {"enabled": false, "config": {"maxEmbedsPerMessage": 3}}
+                },
+                "customCss": ""
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn keys_map_both_ways() {
+        for (section, key, path) in [
+            ("Uprooted", "Enabled", "enabled"),
+            ("Plugins", "themes", "plugins.themes.enabled"),
+            ("Plugin:themes", "theme", "plugins.themes.config.theme"),
+        ] {
+            assert_eq!(path_for(section, key).as_deref(), Some(path));
+            assert_eq!(key_for(path), Some((section.to_string(), key.to_string())));
+        }
+        assert_eq!(path_for("MessageLogger", "RetentionDays"), None);
+        assert_eq!(key_for("customCss"), None);
+
+        // The hook's own plugins share [Plugins] but have no web side
+        assert_eq!(path_for("Plugins", "MessageLogger"), None);
+        assert_eq!(path_for("Plugin:MessageLogger", "MaxMessages"), None);
+        assert_eq!(key_for("plugins.MessageLogger.enabled"), None);
+    }
+
+    #[test]
+    fn reports_and_syncs_disagreements() {
+        let ini = "[Uprooted]\nEnabled=True\n[Plugins]\nthemes=False\nMessageLogger=true\n[Plugin:link-embeds]\nmaxEmbedsPerMessage=3\n";
+        let found = differences(&Ini::parse(ini), &settings());
+        let paths: Vec<&str> = found.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["plugins.link-embeds.enabled", "plugins.themes.config.theme", "plugins.themes.enabled"]
+        );
+        assert_eq!(found[2].ini, Some(Value::Bool(false)));
+        assert_eq!(found[2].json, Some(Value::Bool(true)));
+
+        let mut to_json = settings();
+        assert_eq!(sync_to_json(&Ini::parse(ini), &mut to_json), (1, vec![]));
+        assert!(!to_json.plugins["themes"].enabled);
+        assert!(!to_json.plugins.contains_key("MessageLogger"));
+        assert_eq!(to_json.plugins["themes"].config["theme"], "crimson");
+
+        // Only keys the hook already wrote are updated; a missing one is reported, not added
+        let mut to_ini = Ini::parse(ini);
+        let (changed, skipped) = sync_to_ini(&settings(), &mut to_ini);
+        assert_eq!(changed, 1);
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped[0].starts_with("plugins.link-embeds.enabled"), "{skipped:?}");
+        let rendered = to_ini.render();
+        assert!(rendered.contains("themes=true\n"), "{rendered}");
+        assert!(!rendered.contains("[Plugin:themes]") && !rendered.contains("link-embeds="), "{rendered}");
+        assert!(rendered.contains("MessageLogger=true\n"), "{rendered}");
+    }
+}
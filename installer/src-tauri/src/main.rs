@@ -1,9 +1,27 @@
+mod backups;
+mod bundle;
 mod cli;
+mod csp;
 mod detection;
 mod embedded;
+mod fsutil;
+mod guid;
+mod hash;
 mod hook;
+mod html;
+mod ini;
+mod ini_settings;
+mod manifest;
 mod patcher;
+mod profiles;
+#[cfg(unix)]
+mod quote;
+mod schema;
 mod settings;
+mod stylesheets;
+mod targets;
+mod themes;
+mod watch;
 
 use clap::Parser;
 
@@ -14,10 +32,24 @@ struct Cli {
     #[arg(long)]
     uninstall: bool,
 
-    /// Repair installation (re-deploy files, re-patch HTML)
+    /// Repair installation (re-deploy files, re-patch HTML); settings are kept
     #[arg(long)]
     repair: bool,
 
+    /// With --repair, also reset user data: `settings` (JSON and INI, exported first),
+    /// `message-log`, or `all` (the default); comma-separated
+    #[arg(
+        long,
+        value_enum,
+        value_name = "settings|message-log|all",
+        requires = "repair",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all",
+        value_delimiter = ','
+    )]
+    reset_settings: Vec<hook::UserData>,
+
     /// Run diagnostics (check files, env vars, patches)
     #[arg(long)]
     diagnose: bool,
@@ -25,6 +57,179 @@ struct Cli {
     /// Plain ANSI output instead of TUI (for scripts / CI)
     #[arg(long)]
     plain: bool,
+
+    /// How to inject the preload and theme into Root's HTML: `file://` references or
+    /// embedded inline (for Root builds whose CSP blocks `file:` URLs)
+    #[arg(long, value_enum, default_value = "file")]
+    inject: patcher::InjectMode,
+
+    /// What to do when a Content-Security-Policy in Root's HTML blocks the injection:
+    /// warn, or amend it to allow exactly the injected content (restored on uninstall)
+    #[arg(long, value_enum, default_value = "report")]
+    csp: csp::CspMode,
+
+    /// Start Root with Uprooted at login (Linux, replaces Root's own autostart entry)
+    #[arg(long, value_name = "on|off")]
+    autostart: Option<Toggle>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Snapshots of Root's HTML taken before patching
+    Backups {
+        #[command(subcommand)]
+        action: BackupsAction,
+    },
+    /// Read and edit uprooted-settings.json (patched HTML picks up the change)
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Turn plugins on and off
+    Plugin {
+        #[command(subcommand)]
+        action: PluginAction,
+    },
+    /// Stylesheets from disk, injected after the theme (see `stylesheetInjection`)
+    Css {
+        #[command(subcommand)]
+        action: CssAction,
+    },
+    /// Pick, preview and import themes for the themes plugin
+    Theme {
+        #[command(subcommand)]
+        action: ThemeAction,
+    },
+    /// Move settings, the hook's INI file and custom CSS between machines or profiles
+    Settings {
+        #[command(subcommand)]
+        action: SettingsAction,
+    },
+    /// Named copies of the settings to switch between, e.g. "streaming" and "daily"
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    /// Sync uprooted-settings.json into the patched HTML whenever it is saved (Ctrl+C stops)
+    Watch,
+}
+
+#[derive(clap::Subcommand)]
+enum BackupsAction {
+    /// List snapshots, newest first for each target
+    List,
+    /// Write a snapshot back over its target (this removes Uprooted from that file)
+    Restore {
+        /// Snapshot id, as printed by `backups list`
+        id: String,
+    },
+    /// Delete old snapshots, always keeping each target's pre-install original
+    Prune {
+        /// Snapshots to keep per target
+        #[arg(long, default_value_t = 5)]
+        keep: usize,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum ConfigAction {
+    /// Print the value at a dotted path, e.g. `plugins.themes.config.theme`
+    Get { path: String },
+    /// Set the value at a dotted path (JSON, or a plain string)
+    Set { path: String, value: String },
+    /// Remove the value at a dotted path
+    Unset { path: String },
+    /// Check the settings file (and any set aside as invalid) against the schema
+    Validate,
+    /// Show settings on which the hook's INI file and the JSON file disagree
+    Diff,
+    /// Make one settings file match the other where they overlap
+    Sync {
+        /// File whose values win
+        #[arg(long, value_enum)]
+        from: ini_settings::Source,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum PluginAction {
+    /// List known plugins and whether each is enabled
+    List,
+    /// Enable a plugin
+    Enable { name: String },
+    /// Disable a plugin
+    Disable { name: String },
+    /// Check plugin settings against the plugins this installer ships: unknown plugins
+    /// and keys, values of the wrong type, and defaults left out
+    Lint {
+        /// Rename typos, drop leftovers, reset bad values and fill in defaults
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum CssAction {
+    /// List stylesheets and whether each is fit to inject
+    List,
+    /// Copy a .css file in (replacing one of the same name) after checking its braces
+    Add { file: std::path::PathBuf },
+    /// Remove a stylesheet
+    Remove { name: String },
+}
+
+#[derive(clap::Subcommand)]
+enum ThemeAction {
+    /// List built-in and imported themes
+    List,
+    /// Select a theme
+    Set {
+        name: String,
+        /// Accent colour (#RRGGBB), for the custom theme
+        #[arg(long)]
+        accent: Option<String>,
+        /// Background colour (#RRGGBB), for the custom theme
+        #[arg(long)]
+        background: Option<String>,
+    },
+    /// Show a theme's colours in the terminal (the selected one by default)
+    Preview { name: Option<String> },
+    /// Add a theme from a JSON file in the themes.json entry format
+    Import { file: std::path::PathBuf },
+}
+
+#[derive(clap::Subcommand)]
+enum SettingsAction {
+    /// Write the current settings to a bundle file
+    Export { file: std::path::PathBuf },
+    /// Apply a bundle written by `settings export`
+    Import {
+        file: std::path::PathBuf,
+        /// Merge into the current settings, or replace them
+        #[arg(long, value_enum, default_value = "merge")]
+        mode: bundle::ImportMode,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum ProfileAction {
+    /// List saved profiles, marking one that matches the current settings
+    List,
+    /// Save the current settings as a profile (replacing one of the same name)
+    Save { name: String },
+    /// Make a profile the current settings and re-inject them
+    Switch { name: String },
+    /// Delete a profile
+    Delete { name: String },
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -32,13 +237,56 @@ pub enum InstallerMode {
     Install,
     Uninstall,
     Repair,
+    /// Repair and reset all user data (from the mode selector).
+    RepairAndReset,
 }
 
 fn main() {
     let args = Cli::parse();
+    let patch = patcher::PatchOptions { inject: args.inject, csp: args.csp };
+
+    if let Some(command) = args.command {
+        match command {
+            Command::Backups { action: BackupsAction::List } => cli::run_backups_list(),
+            Command::Backups { action: BackupsAction::Restore { id } } => cli::run_backups_restore(&id),
+            Command::Backups { action: BackupsAction::Prune { keep } } => cli::run_backups_prune(keep),
+            Command::Config { action: ConfigAction::Get { path } } => cli::run_config_get(&path),
+            Command::Config { action: ConfigAction::Set { path, value } } => cli::run_config_set(&path, &value),
+            Command::Config { action: ConfigAction::Unset { path } } => cli::run_config_unset(&path),
+            Command::Config { action: ConfigAction::Validate } => cli::run_config_validate(),
+            Command::Config { action: ConfigAction::Diff } => cli::run_config_diff(),
+            Command::Config { action: ConfigAction::Sync { from } } => cli::run_config_sync(from),
+            Command::Plugin { action: PluginAction::List } => cli::run_plugin_list(),
+            Command::Plugin { action: PluginAction::Enable { name } } => cli::run_plugin_toggle(&name, true),
+            Command::Plugin { action: PluginAction::Disable { name } } => cli::run_plugin_toggle(&name, false),
+            Command::Plugin { action: PluginAction::Lint { fix } } => cli::run_plugin_lint(fix),
+            Command::Css { action: CssAction::List } => cli::run_css_list(),
+            Command::Css { action: CssAction::Add { file } } => cli::run_css_add(&file),
+            Command::Css { action: CssAction::Remove { name } } => cli::run_css_remove(&name),
+            Command::Theme { action: ThemeAction::List } => cli::run_theme_list(),
+            Command::Theme { action: ThemeAction::Set { name, accent, background } } => {
+                cli::run_theme_set(&name, accent.as_deref(), background.as_deref())
+            }
+            Command::Theme { action: ThemeAction::Preview { name } } => cli::run_theme_preview(name.as_deref()),
+            Command::Theme { action: ThemeAction::Import { file } } => cli::run_theme_import(&file),
+            Command::Settings { action: SettingsAction::Export { file } } => cli::run_settings_export(&file),
+            Command::Settings { action: SettingsAction::Import { file, mode } } => cli::run_settings_import(&file, mode),
+            Command::Profile { action: ProfileAction::List } => cli::run_profile_list(),
+            Command::Profile { action: ProfileAction::Save { name } } => cli::run_profile_save(&name),
+            Command::Profile { action: ProfileAction::Switch { name } } => cli::run_profile_switch(&name),
+            Command::Profile { action: ProfileAction::Delete { name } } => cli::run_profile_delete(&name),
+            Command::Watch => cli::run_watch(),
+        }
+        return;
+    }
 
     if args.diagnose {
-        cli::run_diagnose();
+        cli::run_diagnose(patch);
+        return;
+    }
+
+    if let Some(toggle) = args.autostart {
+        cli::run_autostart(toggle == Toggle::On);
         return;
     }
 
@@ -52,13 +300,18 @@ fn main() {
         tui::run_mode_selector()
     };
 
+    let reset = match mode {
+        InstallerMode::RepairAndReset => vec![hook::UserData::All],
+        _ => args.reset_settings,
+    };
+
     match (mode, args.plain) {
-        (InstallerMode::Install, true) => cli::run_install_plain(),
-        (InstallerMode::Install, false) => tui::run_install(),
+        (InstallerMode::Install, true) => cli::run_install_plain(patch),
+        (InstallerMode::Install, false) => tui::run_install(patch),
         (InstallerMode::Uninstall, true) => cli::run_uninstall_plain(),
         (InstallerMode::Uninstall, false) => tui::run_uninstall(),
-        (InstallerMode::Repair, true) => cli::run_repair_plain(),
-        (InstallerMode::Repair, false) => tui::run_repair(),
+        (InstallerMode::Repair | InstallerMode::RepairAndReset, true) => cli::run_repair_plain(patch, &reset),
+        (InstallerMode::Repair | InstallerMode::RepairAndReset, false) => tui::run_repair(patch, &reset),
     }
 }
 
@@ -67,7 +320,8 @@ fn main() {
 // ══════════════════════════════════════════════════════════════════════════════
 
 mod tui {
-    use crate::{detection, hook, patcher};
+    use crate::{bundle, detection, hook, patcher};
+    use crate::patcher::PatchOptions;
     use crossterm::{
         event::{self, Event, KeyCode, KeyEventKind},
         execute,
@@ -267,12 +521,11 @@ mod tui {
                 break;
             }
 
-            if event::poll(timeout.min(Duration::from_millis(100))).unwrap_or(false) {
-                if let Ok(Event::Key(key)) = event::read() {
-                    if key.kind == KeyEventKind::Press {
-                        break;
-                    }
-                }
+            if event::poll(timeout.min(Duration::from_millis(100))).unwrap_or(false)
+                && let Ok(Event::Key(key)) = event::read()
+                && key.kind == KeyEventKind::Press
+            {
+                break;
             }
 
             // Update spinner for any running steps
@@ -285,7 +538,16 @@ mod tui {
         let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
     }
 
-    pub fn run_install() {
+    /// First warning of a step, with a pointer to the rest.
+    fn warning_summary(warnings: &[String]) -> String {
+        match warnings {
+            [only] => only.clone(),
+            [first, rest @ ..] => format!("{} (+{} more, see --diagnose)", first, rest.len()),
+            [] => String::new(),
+        }
+    }
+
+    pub fn run_install(patch: PatchOptions) {
         let state = AppState {
             steps: vec![
                 Step::new("Check for running Root process"),
@@ -329,7 +591,12 @@ mod tui {
             // Step 2: Deploy files
             state.steps[2].status = StepStatus::Running;
             match hook::deploy_files() {
-                Ok(()) => state.steps[2].status = StepStatus::Done,
+                Ok(()) => {
+                    state.steps[2].status = match hook::profiler_manifest_warning() {
+                        Some(warning) => StepStatus::Warn(warning.to_string()),
+                        None => StepStatus::Done,
+                    }
+                }
                 Err(e) => {
                     state.steps[2].status = StepStatus::Failed(e);
                     state.finished = true;
@@ -355,8 +622,10 @@ mod tui {
             // hasn't run Root yet, the profile dir won't have HTML files.
             // The hook self-heals HTML patches at runtime, so this is best-effort.
             state.steps[4].status = StepStatus::Running;
-            let result = patcher::install();
-            if result.success {
+            let result = patcher::install(patch);
+            if result.success && !result.warnings.is_empty() {
+                state.steps[4].status = StepStatus::Warn(warning_summary(&result.warnings));
+            } else if result.success {
                 state.steps[4].status = StepStatus::Done;
             } else {
                 state.steps[4].status = StepStatus::Warn(format!(
@@ -368,7 +637,12 @@ mod tui {
             // Step 5: Verify
             state.steps[5].status = StepStatus::Running;
             let final_check = detection::detect();
-            if final_check.hook_status.files_ok && final_check.is_installed {
+            if !final_check.integrity_mismatches.is_empty() {
+                state.steps[5].status = StepStatus::Warn(format!(
+                    "Integrity mismatch: {}",
+                    warning_summary(&final_check.integrity_mismatches)
+                ));
+            } else if final_check.hook_status.files_ok && final_check.is_installed {
                 state.steps[5].status = StepStatus::Done;
             } else {
                 state.steps[5].status = StepStatus::Warn("Verification found issues — hook files deployed, HTML patches pending.".to_string());
@@ -422,7 +696,9 @@ mod tui {
             // Step 2: Restore HTML
             state.steps[2].status = StepStatus::Running;
             let result = patcher::uninstall();
-            if result.success {
+            if result.success && !result.warnings.is_empty() {
+                state.steps[2].status = StepStatus::Warn(warning_summary(&result.warnings));
+            } else if result.success {
                 state.steps[2].status = StepStatus::Done;
             } else {
                 state.steps[2].status = StepStatus::Failed(result.message);
@@ -430,7 +706,7 @@ mod tui {
 
             // Step 3: Remove settings
             state.steps[3].status = StepStatus::Running;
-            match hook::reset_settings() {
+            match hook::reset_settings(&[hook::UserData::All]) {
                 Ok(n) => {
                     state.steps[3].label = format!("Settings removed ({} file{})", n, if n == 1 { "" } else { "s" });
                     state.steps[3].status = StepStatus::Done;
@@ -459,17 +735,22 @@ mod tui {
         });
     }
 
-    pub fn run_repair() {
+    pub fn run_repair(patch: PatchOptions, reset: &[hook::UserData]) {
+        let what = hook::UserData::describe(reset);
         let state = AppState {
             steps: vec![
                 Step::new("Check for running Root process"),
-                Step::new("Reset settings (plugins, themes, preferences)"),
+                if reset.is_empty() {
+                    Step::new("Keep settings (plugins, themes, preferences)")
+                } else {
+                    Step::new(&format!("Reset {}", what.to_lowercase()))
+                },
                 Step::new("Re-deploy hook files"),
                 Step::new("Set environment variables"),
                 Step::new("Re-patch HTML files"),
                 Step::new("Verify installation"),
             ],
-            title: "Repair (resets all settings)",
+            title: if reset.is_empty() { "Repair" } else { "Repair (resets settings)" },
             finished: false,
             success: false,
             message: String::new(),
@@ -487,25 +768,51 @@ mod tui {
             }
             state.steps[0].status = StepStatus::Done;
 
-            // Step 1: Reset settings
+            // Step 1: Keep settings, or export them and reset what was asked for
             state.steps[1].status = StepStatus::Running;
-            match hook::reset_settings() {
-                Ok(n) => {
-                    state.steps[1].label = format!("Settings reset ({} file{} removed)", n, if n == 1 { "" } else { "s" });
-                    state.steps[1].status = StepStatus::Done;
-                }
-                Err(e) => {
-                    state.steps[1].status = StepStatus::Failed(e);
-                    state.finished = true;
-                    state.message = "Repair failed.".to_string();
-                    return;
+            if reset.is_empty() {
+                state.steps[1].label = "Settings kept (pass --reset-settings to reset them)".to_string();
+                state.steps[1].status = StepStatus::Done;
+            } else {
+                let exported = if hook::UserData::includes_settings(reset) {
+                    match bundle::export_before_reset() {
+                        Ok(path) => path,
+                        Err(e) => {
+                            state.steps[1].status = StepStatus::Failed(format!("Export failed, not resetting: {}", e));
+                            state.finished = true;
+                            state.message = "Repair failed.".to_string();
+                            return;
+                        }
+                    }
+                } else {
+                    None
+                };
+                match hook::reset_settings(reset) {
+                    Ok(n) => {
+                        state.steps[1].label = format!("{} reset ({} file{} removed)", what, n, if n == 1 { "" } else { "s" });
+                        if let Some(path) = exported {
+                            state.steps[1].label.push_str(&format!(", exported to {}", path.display()));
+                        }
+                        state.steps[1].status = StepStatus::Done;
+                    }
+                    Err(e) => {
+                        state.steps[1].status = StepStatus::Failed(e);
+                        state.finished = true;
+                        state.message = "Repair failed.".to_string();
+                        return;
+                    }
                 }
             }
 
             // Step 2: Deploy files
             state.steps[2].status = StepStatus::Running;
             match hook::deploy_files() {
-                Ok(()) => state.steps[2].status = StepStatus::Done,
+                Ok(()) => {
+                    state.steps[2].status = match hook::profiler_manifest_warning() {
+                        Some(warning) => StepStatus::Warn(warning.to_string()),
+                        None => StepStatus::Done,
+                    }
+                }
                 Err(e) => {
                     state.steps[2].status = StepStatus::Failed(e);
                     state.finished = true;
@@ -528,8 +835,10 @@ mod tui {
 
             // Step 4: Repair HTML (non-fatal, same reasoning as install)
             state.steps[4].status = StepStatus::Running;
-            let result = patcher::repair();
-            if result.success {
+            let result = patcher::repair(patch);
+            if result.success && !result.warnings.is_empty() {
+                state.steps[4].status = StepStatus::Warn(warning_summary(&result.warnings));
+            } else if result.success {
                 state.steps[4].status = StepStatus::Done;
             } else {
                 state.steps[4].status = StepStatus::Warn(format!(
@@ -541,7 +850,12 @@ mod tui {
             // Step 5: Verify
             state.steps[5].status = StepStatus::Running;
             let final_check = detection::detect();
-            if final_check.hook_status.files_ok && final_check.is_installed {
+            if !final_check.integrity_mismatches.is_empty() {
+                state.steps[5].status = StepStatus::Warn(format!(
+                    "Integrity mismatch: {}",
+                    warning_summary(&final_check.integrity_mismatches)
+                ));
+            } else if final_check.hook_status.files_ok && final_check.is_installed {
                 state.steps[5].status = StepStatus::Done;
             } else {
                 state.steps[5].status = StepStatus::Warn("Verification found issues — hook files deployed, HTML patches pending.".to_string());
@@ -557,7 +871,8 @@ mod tui {
         const ITEMS: &[(&str, &str, crate::InstallerMode)] = &[
             ("Install", "", crate::InstallerMode::Install),
             ("Uninstall", "", crate::InstallerMode::Uninstall),
-            ("Repair", "resets all settings & plugins", crate::InstallerMode::Repair),
+            ("Repair", "keeps settings & plugins", crate::InstallerMode::Repair),
+            ("Repair + reset", "resets all settings & plugins", crate::InstallerMode::RepairAndReset),
         ];
 
         let _ = enable_raw_mode();
@@ -632,25 +947,24 @@ mod tui {
                 frame.render_widget(Paragraph::new(lines), inner);
             });
 
-            if event::poll(Duration::from_millis(100)).unwrap_or(false) {
-                if let Ok(Event::Key(key)) = event::read() {
-                    if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Up => {
-                                if selected > 0 { selected -= 1; }
-                            }
-                            KeyCode::Down => {
-                                if selected < ITEMS.len() - 1 { selected += 1; }
-                            }
-                            KeyCode::Enter => break,
-                            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
-                                let _ = disable_raw_mode();
-                                let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
-                                std::process::exit(0);
-                            }
-                            _ => {}
-                        }
+            if event::poll(Duration::from_millis(100)).unwrap_or(false)
+                && let Ok(Event::Key(key)) = event::read()
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Up => {
+                        selected = selected.saturating_sub(1);
+                    }
+                    KeyCode::Down => {
+                        selected = (selected + 1).min(ITEMS.len() - 1);
+                    }
+                    KeyCode::Enter => break,
+                    KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
+                        let _ = disable_raw_mode();
+                        let _ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
+                        std::process::exit(0);
                     }
+                    _ => {}
                 }
             }
         }
@@ -0,0 +1,356 @@
+//! What the plugins this installer ships accept in their config, and a lint of the
+//! settings' `plugins` map against it (`uprooted plugin lint [--fix]`).
+//!
+//! The config keys mirror each plugin's `settings` definition (`src/plugins/*/index.ts`),
+//! plus keys a plugin reads without declaring them. `plugins` is free-form as far as the
+//! schema goes, so this is where typos and leftovers from removed plugins are caught.
+
+use crate::schema::{join, Issue};
+use crate::settings::UprootedSettings;
+use crate::themes;
+use serde_json::Value;
+
+/// Type of a config value.
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Boolean,
+    String,
+    Number { min: f64, max: f64 },
+    /// A string out of the options the function lists.
+    Select(fn() -> Vec<String>),
+    /// An object of strings.
+    StringMap,
+}
+
+pub struct ConfigKey {
+    pub key: &'static str,
+    pub kind: Kind,
+    /// Default as JSON, for keys the plugin declares in its `settings`; `None` for keys
+    /// it only reads, falling back to a value of its own when they are absent.
+    pub default: Option<&'static str>,
+}
+
+pub struct PluginManifest {
+    pub name: &'static str,
+    /// One line for `uprooted plugin list`.
+    pub description: &'static str,
+    /// `None` when the installer doesn't know the plugin's config (it ships a script
+    /// built elsewhere), which is then left unchecked.
+    pub config: Option<&'static [ConfigKey]>,
+}
+
+/// Plugins the preload registers (see `src/core/preload.ts`) plus the standalone plugin
+/// scripts: every web plugin this installer knows.
+pub const PLUGINS: &[PluginManifest] = &[
+    PluginManifest {
+        name: "sentry-blocker",
+        description: "Blocks Sentry error tracking to protect your privacy",
+        config: Some(&[]),
+    },
+    PluginManifest {
+        name: "themes",
+        description: "Built-in theme engine for Root Communications",
+        config: Some(&[
+            ConfigKey { key: "theme", kind: Kind::Select(theme_names), default: Some("\"default\"") },
+            ConfigKey { key: "customAccent", kind: Kind::String, default: None },
+            ConfigKey { key: "customBackground", kind: Kind::String, default: None },
+            ConfigKey { key: "variables", kind: Kind::StringMap, default: None },
+        ]),
+    },
+    PluginManifest {
+        name: "settings-panel",
+        description: "In-app settings panel injected into Root's settings sidebar",
+        config: Some(&[]),
+    },
+    PluginManifest {
+        name: "link-embeds",
+        description: "Discord-style link previews for URLs in chat",
+        config: Some(&[
+            ConfigKey { key: "youtube", kind: Kind::Boolean, default: Some("true") },
+            ConfigKey { key: "websites", kind: Kind::Boolean, default: Some("true") },
+            ConfigKey { key: "maxEmbedsPerMessage", kind: Kind::Number { min: 1.0, max: 10.0 }, default: Some("3") },
+        ]),
+    },
+    PluginManifest { name: "silent-typing", description: "Hide that you are typing", config: Some(&[]) },
+    PluginManifest { name: "nsfw-filter", description: "Blurs NSFW images and videos in chat", config: None },
+];
+
+/// Built-in and imported themes.
+fn theme_names() -> Vec<String> {
+    themes::builtin().into_iter().chain(themes::imported()).map(|t| t.name).collect()
+}
+
+pub fn find(name: &str) -> Option<&'static PluginManifest> {
+    PLUGINS.iter().find(|p| p.name == name)
+}
+
+impl ConfigKey {
+    fn default_value(&self) -> Option<Value> {
+        self.default.and_then(|json| serde_json::from_str(json).ok())
+    }
+
+    /// Why `value` doesn't fit, if it doesn't.
+    fn check(&self, value: &Value) -> Option<String> {
+        let fits = match self.kind {
+            Kind::Boolean => value.is_boolean(),
+            Kind::String => value.is_string(),
+            Kind::Number { min, max } => {
+                if let Some(n) = value.as_f64()
+                    && !(min..=max).contains(&n)
+                {
+                    return Some(format!("{} is out of range ({}-{})", n, min, max));
+                }
+                value.is_number()
+            }
+            Kind::Select(options) => {
+                let options = options();
+                if let Some(s) = value.as_str()
+                    && !options.iter().any(|o| o == s)
+                {
+                    return Some(format!("{:?} is not one of {}", s, options.join(", ")));
+                }
+                value.is_string()
+            }
+            Kind::StringMap => value.as_object().is_some_and(|map| map.values().all(Value::is_string)),
+        };
+        let expected = match self.kind {
+            Kind::Boolean => "a boolean",
+            Kind::String | Kind::Select(_) => "a string",
+            Kind::Number { .. } => "a number",
+            Kind::StringMap => "an object of strings",
+        };
+        (!fits).then(|| format!("expected {}, found {}", expected, value))
+    }
+}
+
+/// How `--fix` resolves a [`Finding`].
+#[derive(Debug, PartialEq)]
+pub enum Fix {
+    RenamePlugin { to: String },
+    RemovePlugin,
+    RenameKey { to: String },
+    /// Drop the key, so the plugin falls back to its own default.
+    RemoveKey,
+    SetValue(Value),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub issue: Issue,
+    /// `None` when it needs a person to decide.
+    pub fix: Option<Fix>,
+    plugin: String,
+    key: Option<String>,
+}
+
+impl Finding {
+    /// What applying the fix does, e.g. `renamed to link-embeds`.
+    pub fn fix_description(&self) -> Option<String> {
+        Some(match self.fix.as_ref()? {
+            Fix::RenamePlugin { to } | Fix::RenameKey { to } => format!("renamed to {}", to),
+            Fix::RemovePlugin | Fix::RemoveKey => "removed".to_string(),
+            Fix::SetValue(value) => format!("set to {}", value),
+        })
+    }
+}
+
+/// Loose form of a name for spotting typos: `link_embeds`, `LinkEmbeds` and
+/// `link-embeds` all match.
+fn loose(name: &str) -> String {
+    name.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_lowercase()).collect()
+}
+
+/// Check `settings.plugins` against [`PLUGINS`], sorted by path.
+pub fn lint(settings: &UprootedSettings) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let finding = |plugin: &str, key: Option<&str>, message: String, fix: Option<Fix>| {
+        let mut path = join("plugins", plugin);
+        if let Some(key) = key {
+            path = join(&join(&path, "config"), key);
+        }
+        Finding { issue: Issue { path, message }, fix, plugin: plugin.to_string(), key: key.map(str::to_string) }
+    };
+
+    for (name, plugin) in &settings.plugins {
+        let Some(manifest) = find(name) else {
+            let finding = match PLUGINS.iter().find(|p| loose(p.name) == loose(name)) {
+                Some(known) if settings.plugins.contains_key(known.name) => {
+                    finding(name, None, format!("unknown plugin, and {} is set as well", known.name), None)
+                }
+                Some(known) => finding(
+                    name,
+                    None,
+                    format!("unknown plugin, did you mean {}?", known.name),
+                    Some(Fix::RenamePlugin { to: known.name.to_string() }),
+                ),
+                None => finding(name, None, "unknown plugin".to_string(), Some(Fix::RemovePlugin)),
+            };
+            findings.push(finding);
+            continue;
+        };
+        let Some(keys) = manifest.config else {
+            continue;
+        };
+
+        for (key, value) in &plugin.config {
+            let Some(spec) = keys.iter().find(|k| k.key == key) else {
+                let finding = match keys.iter().find(|k| loose(k.key) == loose(key)) {
+                    Some(known) if plugin.config.contains_key(known.key) => {
+                        finding(name, Some(key), format!("unknown key, and {} is set as well", known.key), None)
+                    }
+                    Some(known) => finding(
+                        name,
+                        Some(key),
+                        format!("unknown key, did you mean {}?", known.key),
+                        Some(Fix::RenameKey { to: known.key.to_string() }),
+                    ),
+                    None => finding(name, Some(key), "unknown key".to_string(), Some(Fix::RemoveKey)),
+                };
+                findings.push(finding);
+                continue;
+            };
+            if let Some(problem) = spec.check(value) {
+                let fix = spec.default_value().map_or(Fix::RemoveKey, Fix::SetValue);
+                findings.push(finding(name, Some(key), problem, Some(fix)));
+            }
+        }
+
+        // A misspelt key is reported (and renamed) above, not as missing as well
+        for spec in keys.iter().filter(|k| !plugin.config.keys().any(|key| loose(key) == loose(k.key))) {
+            if let Some(default) = spec.default_value() {
+                let message = format!("missing, the plugin defaults to {}", default);
+                findings.push(finding(name, Some(spec.key), message, Some(Fix::SetValue(default))));
+            }
+        }
+    }
+    findings.sort_by(|a, b| a.issue.path.cmp(&b.issue.path));
+    findings
+}
+
+/// Apply every fix [`lint`] finds, linting again after renames so a renamed plugin's
+/// config is checked too. Returns the findings fixed.
+pub fn fix(settings: &mut UprootedSettings) -> Vec<Finding> {
+    let mut fixed = Vec::new();
+    loop {
+        let (fixable, _): (Vec<Finding>, Vec<Finding>) = lint(settings).into_iter().partition(|f| f.fix.is_some());
+        if fixable.is_empty() {
+            return fixed;
+        }
+        apply(settings, &fixable);
+        fixed.extend(fixable);
+    }
+}
+
+fn apply(settings: &mut UprootedSettings, findings: &[Finding]) {
+    for finding in findings {
+        let Some(fix) = &finding.fix else {
+            continue;
+        };
+        let plugins = &mut settings.plugins;
+        match (fix, &finding.key) {
+            (Fix::RenamePlugin { to }, _) => {
+                if let Some(plugin) = plugins.remove(&finding.plugin) {
+                    plugins.entry(to.clone()).or_insert(plugin);
+                }
+            }
+            (Fix::RemovePlugin, _) => {
+                plugins.remove(&finding.plugin);
+            }
+            (_, None) => {}
+            (fix, Some(key)) => {
+                let Some(config) = plugins.get_mut(&finding.plugin).map(|p| &mut p.config) else {
+                    continue;
+                };
+                match fix {
+                    Fix::RenameKey { to } => {
+                        if let Some(value) = config.remove(key) {
+                            config.entry(to.clone()).or_insert(value);
+                        }
+                    }
+                    Fix::SetValue(value) => {
+                        config.insert(key.clone(), value.clone());
+                    }
+                    _ => {
+                        config.remove(key);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_follows_the_plugin_sources() {
+        let preload = include_str!("../../../src/core/preload.ts");
+        for plugin in PLUGINS.iter().filter(|p| p.config.is_some()) {
+            assert!(preload.contains(&format!("plugins/{}/index", plugin.name)), "{}", plugin.name);
+        }
+        let sources = [
+            ("link-embeds", include_str!("../../../src/plugins/link-embeds/index.ts")),
+            ("themes", include_str!("../../../src/plugins/themes/index.ts")),
+        ];
+        for (name, source) in sources {
+            for key in find(name).unwrap().config.unwrap() {
+                let declared = source.contains(&format!("    {}: {{\n      type:", key.key));
+                assert_eq!(declared, key.default.is_some(), "{}.{}", name, key.key);
+                if let Some(default) = key.default {
+                    assert!(source.contains(&format!("default: {},", default)), "{}.{}", name, key.key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn typos_types_and_defaults_are_found_and_fixed() {
+        let mut settings: UprootedSettings = serde_json::from_str(
+            r#"{"enabled": true, "customCss": "", "plugins": {
+                "link_embeds": {"enabled": true, "config": {"youtube": "yes", "maxEmbeds": 2, "websites": true, "maxEmbedsPerMessage": 20}},
+                "old-plugin": {"enabled": true, "config": {}},
+                "Silent_Typing": {"enabled": true, "config": {}},
+                "silent-typing": {"enabled": false, "config": {}},
+                "themes": {"enabled": true, "config": {"Theme": "default", "customAccent": 5}}
+            }}"#,
+        )
+        .unwrap();
+        let found = lint(&settings);
+        let shown: Vec<String> = found.iter().map(|f| f.issue.to_string()).collect();
+        assert_eq!(
+            shown,
+            [
+                "plugins.Silent_Typing: unknown plugin, and silent-typing is set as well",
+                "plugins.link_embeds: unknown plugin, did you mean link-embeds?",
+                "plugins.old-plugin: unknown plugin",
+                "plugins.themes.config.Theme: unknown key, did you mean theme?",
+                "plugins.themes.config.customAccent: expected a string, found 5",
+            ]
+        );
+        assert_eq!(found[0].fix, None);
+
+        // The renamed plugin's config is checked once it has its proper name
+        let fixed: Vec<String> = fix(&mut settings).iter().map(|f| f.issue.to_string()).collect();
+        assert_eq!(
+            fixed[4..],
+            [
+                "plugins.link-embeds.config.maxEmbeds: unknown key",
+                "plugins.link-embeds.config.maxEmbedsPerMessage: 20 is out of range (1-10)",
+                "plugins.link-embeds.config.youtube: expected a boolean, found \"yes\"",
+            ]
+        );
+        assert_eq!(lint(&settings).len(), 1);
+        let config = |plugin: &str| &settings.plugins[plugin].config;
+        assert_eq!(config("link-embeds")["maxEmbedsPerMessage"], 3);
+        assert_eq!(config("link-embeds")["youtube"], true);
+        assert!(!config("link-embeds").contains_key("maxEmbeds"));
+        assert_eq!(config("themes")["theme"], "default");
+        assert!(!config("themes").contains_key("customAccent"));
+        assert!(!settings.plugins.contains_key("old-plugin"));
+
+        settings.plugins.get_mut("link-embeds").unwrap().config.clear();
+        let missing: Vec<String> = lint(&settings).iter().skip(1).map(|f| f.issue.to_string()).collect();
+        assert_eq!(missing[0], "plugins.link-embeds.config.maxEmbedsPerMessage: missing, the plugin defaults to 3");
+    }
+}
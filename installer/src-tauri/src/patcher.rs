@@ -1,21 +1,73 @@
-use crate::detection::find_target_html_files;
+use crate::backups::BackupStore;
+use crate::csp::{self, CspMode};
+use crate::detection::{self, find_known_html_files, find_target_html_files, get_profile_dir};
+use crate::embedded;
+use crate::fsutil::write_atomic;
+use crate::hash;
 use crate::hook;
-use crate::settings::load_settings;
+use crate::html;
+use crate::settings::{load_settings, InjectionProfile, UprootedSettings, FULL_PROFILE};
+use crate::stylesheets;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-const MARKER_START: &str = "<!-- uprooted:start -->";
+/// Start marker prefix; the full marker records the writer, e.g.
+/// `<!-- uprooted:start v0.5.0 file sha256-0123456789abcdef -->`.
+const MARKER_START: &str = "<!-- uprooted:start";
 const MARKER_END: &str = "<!-- uprooted:end -->";
 /// Legacy marker for detection of older installs
 const LEGACY_MARKER: &str = "<!-- uprooted -->";
-const BACKUP_SUFFIX: &str = ".uprooted.bak";
+/// Single backup written next to each target by older installers (now in `backups`)
+const LEGACY_BACKUP_SUFFIX: &str = ".uprooted.bak";
+
+/// How the preload script and theme CSS are referenced from the patched HTML.
+#[derive(Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum InjectMode {
+    /// `<script src>` / `<link href>` pointing at `file://` URLs in the uprooted dir.
+    #[default]
+    File,
+    /// Script and CSS contents embedded in the HTML, for Root builds whose CSP or
+    /// origin refuses `file:` URLs.
+    Inline,
+}
+
+impl InjectMode {
+    fn name(self) -> &'static str {
+        match self {
+            InjectMode::File => "file",
+            InjectMode::Inline => "inline",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [InjectMode::File, InjectMode::Inline].into_iter().find(|m| m.name() == name)
+    }
+}
+
+/// How install and repair patch each target.
+#[derive(Clone, Copy, Default)]
+pub struct PatchOptions {
+    pub inject: InjectMode,
+    pub csp: CspMode,
+}
+
+/// Standalone plugin scripts that inline mode embeds when the plugin is enabled.
+const PLUGIN_SCRIPTS: &[(&str, &[u8])] = &[
+    ("nsfw-filter", embedded::NSFW_FILTER_JS),
+    ("link-embeds", embedded::LINK_EMBEDS_JS),
+];
 
 #[derive(Serialize)]
 pub struct PatchResult {
     pub success: bool,
     pub message: String,
     pub files_patched: Vec<String>,
+    /// Injection profile applied to each target, keyed by path (install and repair only).
+    pub profiles: HashMap<String, String>,
+    /// Non-fatal problems, e.g. a CSP that blocks part of the injection.
+    pub warnings: Vec<String>,
 }
 
 /// Check whether a file contains any uprooted injection.
@@ -25,113 +77,578 @@ pub fn is_patched(content: &str) -> bool {
         || content.contains("uprooted-preload")
 }
 
-pub fn install() -> PatchResult {
+/// How a target's injection compares with what this installer would write now.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchStatus {
+    Unpatched,
+    /// Written by this version, with the current settings and profile.
+    Current,
+    /// Written by an older installer, or with since-changed settings, profile or paths.
+    Outdated,
+    /// Edited after it was written (the payload no longer matches its recorded hash).
+    Foreign,
+}
+
+impl std::fmt::Display for PatchStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            PatchStatus::Unpatched => "unpatched",
+            PatchStatus::Current => "current",
+            PatchStatus::Outdated => "outdated",
+            PatchStatus::Foreign => "foreign",
+        })
+    }
+}
+
+/// An injection block as found in a patched file.
+struct InjectedBlock<'a> {
+    version: &'a str,
+    inject: Option<InjectMode>,
+    hash: &'a str,
+    body: &'a str,
+}
+
+/// The versioned block in `content`; `None` for unpatched files and for injections
+/// from installers that didn't version them.
+fn parse_block(content: &str) -> Option<InjectedBlock<'_>> {
+    let start = content.find(MARKER_START)? + MARKER_START.len();
+    let header_end = start + content[start..].find("-->")?;
+    let mut fields = content[start..header_end].split_whitespace();
+    let version = fields.next()?.strip_prefix('v')?;
+    let inject = InjectMode::from_name(fields.next()?);
+    let hash = fields.next()?.strip_prefix("sha256-")?;
+
+    let body_start = header_end + "-->".len();
+    let body_end = body_start + content[body_start..].find(MARKER_END)?;
+    Some(InjectedBlock { version, inject, hash, body: &content[body_start..body_end] })
+}
+
+/// Hash recorded in the start marker; indentation around the body doesn't count.
+fn payload_hash(body: &str) -> String {
+    hash::sha256_hex(body.trim().as_bytes())[..16].to_string()
+}
+
+/// Wrap an injection body in versioned markers.
+fn injection_block(inject: InjectMode, body: &str) -> String {
+    format!(
+        "{} v{} {} sha256-{} -->\n{}    {}",
+        MARKER_START,
+        env!("CARGO_PKG_VERSION"),
+        inject.name(),
+        payload_hash(body),
+        body,
+        MARKER_END
+    )
+}
+
+/// Status of `content` against `expected`, the body this installer would inject in `inject`
+/// mode (`None` when its profile injects nothing).
+fn block_status(content: &str, inject: InjectMode, expected: Option<&str>) -> PatchStatus {
+    if !is_patched(content) {
+        return PatchStatus::Unpatched;
+    }
+    let Some(block) = parse_block(content) else {
+        return PatchStatus::Outdated;
+    };
+    if payload_hash(block.body) != block.hash {
+        return PatchStatus::Foreign;
+    }
+    let current = block.version == env!("CARGO_PKG_VERSION")
+        && block.inject == Some(inject)
+        && expected.is_some_and(|body| body.trim() == block.body.trim());
+    if current { PatchStatus::Current } else { PatchStatus::Outdated }
+}
+
+/// Status of a target file, compared in the inject mode it was patched with.
+pub fn patch_status(file: &Path, content: &str) -> PatchStatus {
+    let inject = parse_block(content).and_then(|b| b.inject).unwrap_or_default();
+    let (_, expected) = target_injection(&load_settings(), file, inject, &mut vec![]);
+    block_status(content, inject, expected.as_deref())
+}
+
+/// Injection profile name and body for `file`; a profile that can't be resolved falls
+/// back to the full one, with a warning.
+fn target_injection(
+    settings: &UprootedSettings,
+    file: &Path,
+    inject: InjectMode,
+    warnings: &mut Vec<String>,
+) -> (String, Option<String>) {
+    let profile_dir = get_profile_dir();
+    let relative = file.strip_prefix(&profile_dir).unwrap_or(file);
+    let (profile_name, profile) = settings.injection_profile(relative).unwrap_or_else(|e| {
+        warnings.push(format!("{}: {}, using the {} profile", file.display(), e, FULL_PROFILE));
+        (FULL_PROFILE.to_string(), InjectionProfile::default())
+    });
+    let target_settings = settings.for_profile(&profile);
+    let mut body = match inject {
+        InjectMode::File => file_injection(&target_settings, &profile),
+        InjectMode::Inline => inline_injection(&target_settings, &profile),
+    };
+    if profile.theme
+        && let Some(body) = &mut body
+    {
+        body.push_str(&stylesheet_tags(inject, &stylesheets::tags_for(settings, warnings)));
+    }
+    (profile_name, body)
+}
+
+/// Tags injecting user stylesheets after the theme: `file://` links pinned by integrity
+/// like the theme's, or `<style>` elements in inline mode.
+fn stylesheet_tags(inject: InjectMode, sheets: &[(stylesheets::Stylesheet, String)]) -> String {
+    let mut tags = String::new();
+    for (sheet, css) in sheets {
+        match inject {
+            InjectMode::File => tags.push_str(&format!(
+                "    <link rel=\"stylesheet\" href=\"{}\" integrity=\"sha384-{}\">\n",
+                html::escape_attribute(&file_url(&sheet.path)),
+                hash::sha384_base64(css.as_bytes())
+            )),
+            InjectMode::Inline => tags.push_str(&format!(
+                "    <style data-uprooted-stylesheet=\"{}\">\n{}\n</style>\n",
+                html::escape_attribute(&sheet.name),
+                escape_inline_style(css.trim_end())
+            )),
+        }
+    }
+    tags
+}
+
+pub fn install(options: PatchOptions) -> PatchResult {
+    patch_targets(options, false)
+}
+
+/// Injection body that references the deployed preload and theme CSS by `file://` URL.
+///
+/// `None` when the profile injects neither.
+fn file_injection(settings: &UprootedSettings, profile: &InjectionProfile) -> Option<String> {
+    if !profile.preload && !profile.theme {
+        return None;
+    }
     let uprooted_dir = hook::get_uprooted_dir();
+    let preload_url = html::escape_attribute(&file_url(&uprooted_dir.join("uprooted-preload.js")));
+    let css_url = html::escape_attribute(&file_url(&uprooted_dir.join("uprooted.css")));
 
-    let preload_path = uprooted_dir
-        .join("uprooted-preload.js")
-        .to_string_lossy()
-        .replace('\\', "/");
-    let css_path = uprooted_dir
-        .join("uprooted.css")
-        .to_string_lossy()
-        .replace('\\', "/");
+    // Pin what deploy_files writes rather than what is on disk now, so a file replaced
+    // before install is refused as well as one replaced after
+    let preload_integrity = format!("sha384-{}", hash::sha384_base64(embedded::PRELOAD_JS));
+    let css_integrity = format!("sha384-{}", hash::sha384_base64(embedded::THEME_CSS));
 
-    let settings = load_settings();
+    let mut body = String::new();
+    if profile.preload {
+        body.push_str(&format!(
+            "    <script>window.__UPROOTED_SETTINGS__={};</script>\n    <script src=\"{}\" integrity=\"{}\"></script>\n",
+            settings_script_json(settings),
+            preload_url,
+            preload_integrity
+        ));
+    }
+    if profile.theme {
+        body.push_str(&format!(
+            "    <link rel=\"stylesheet\" href=\"{}\" integrity=\"{}\">\n",
+            css_url, css_integrity
+        ));
+    }
+    Some(body)
+}
+
+/// Compare the `integrity` hashes recorded in a patched file with the files they point at.
+///
+/// Returns one line per referenced file that is missing or no longer matches.
+pub fn integrity_mismatches(content: &str) -> Vec<String> {
+    let (Some(start), Some(end)) = (content.find(MARKER_START), content.find(MARKER_END)) else {
+        return vec![];
+    };
+    let Some(block) = content.get(start..end) else {
+        return vec![];
+    };
+
+    let mut mismatches = Vec::new();
+    for tag in html::start_tags(block) {
+        let attrs = html::attributes(block, &tag);
+        let value = |name: &str| attrs.iter().find(|a| a.name == name).and_then(|a| a.value.clone());
+        let (Some(url), Some(integrity)) = (value("src").or_else(|| value("href")), value("integrity")) else {
+            continue;
+        };
+        let Some(path) = file_url_path(&url) else {
+            continue;
+        };
+        let Some(expected) = integrity.strip_prefix("sha384-") else {
+            mismatches.push(format!("{}: unsupported integrity {}", path.display(), integrity));
+            continue;
+        };
+        match fs::read(&path) {
+            Ok(data) if hash::sha384_base64(&data) == expected => {}
+            Ok(_) => mismatches.push(format!("{} changed since it was patched in", path.display())),
+            Err(e) => mismatches.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    mismatches
+}
+
+/// `file://` URL of a local path: `file:///home/...` on Linux, `file:///C:/...` on Windows.
+///
+/// Not escaped for HTML or CSS; [`file_url_path`] turns it back into the path.
+pub fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    if path.starts_with('/') { format!("file://{}", path) } else { format!("file:///{}", path) }
+}
+
+/// Local path of a `file://` URL written by `file_injection`.
+pub fn file_url_path(url: &str) -> Option<std::path::PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    let path = if cfg!(target_os = "windows") { path.trim_start_matches('/') } else { path };
+    Some(std::path::PathBuf::from(path))
+}
+
+/// Injection body that embeds the preload, enabled plugin scripts and theme CSS directly.
+fn inline_injection(settings: &UprootedSettings, profile: &InjectionProfile) -> Option<String> {
+    if !profile.preload && !profile.theme {
+        return None;
+    }
+    let mut body = String::new();
+    if profile.preload {
+        body.push_str(&format!(
+            "    <script>window.__UPROOTED_SETTINGS__={};</script>\n    <script>\n{}\n</script>\n",
+            settings_script_json(settings),
+            escape_inline_script(String::from_utf8_lossy(embedded::PRELOAD_JS).trim_end())
+        ));
+        // for_profile has already disabled plugins the profile doesn't allow
+        for (name, script) in PLUGIN_SCRIPTS {
+            if settings.plugins.get(*name).is_some_and(|p| p.enabled) {
+                body.push_str(&format!(
+                    "    <script data-uprooted-plugin=\"{}\">\n{}\n</script>\n",
+                    name,
+                    escape_inline_script(String::from_utf8_lossy(script).trim_end())
+                ));
+            }
+        }
+    }
+    if profile.theme {
+        body.push_str(&format!(
+            "    <style data-uprooted-theme>\n{}\n</style>\n",
+            escape_inline_style(String::from_utf8_lossy(embedded::THEME_CSS).trim_end())
+        ));
+    }
+    Some(body)
+}
+
+/// Settings JSON for `window.__UPROOTED_SETTINGS__`.
+fn settings_script_json(settings: &UprootedSettings) -> String {
+    // Go through a `Value` (sorted keys) so the same settings always give the same line;
+    // the plugin maps are `HashMap`s, whose order differs from one load to the next.
     // Escape `</` to `<\/` so JSON containing "</script>" can't break out of the script tag
-    let settings_json = serde_json::to_string(&settings)
+    serde_json::to_value(settings)
+        .and_then(|value| serde_json::to_string(&value))
         .unwrap_or_else(|_| "{}".to_string())
-        .replace("</", "<\\/");
-
-    // On Linux, paths start with `/` so `file://` + `/home/...` = `file:///home/...` (correct).
-    // On Windows, paths start with `C:\` so we need `file:///` to get `file:///C:/...`.
-    let file_prefix = if cfg!(target_os = "windows") { "file:///" } else { "file://" };
-
-    let injection = format!(
-        "{start}\n    <script>window.__UPROOTED_SETTINGS__={settings};</script>\n    <script src=\"{prefix}{preload}\"></script>\n    <link rel=\"stylesheet\" href=\"{prefix}{css}\">\n    {end}",
-        start = MARKER_START,
-        end = MARKER_END,
-        settings = settings_json,
-        prefix = file_prefix,
-        preload = preload_path,
-        css = css_path,
-    );
+        .replace("</", "<\\/")
+}
+
+/// Make JavaScript safe to place inside `<script>...</script>`.
+///
+/// `</script` would end the element and `<!--` can switch the parser into its
+/// script-data-escaped states; `<\/` and `<\!` mean the same inside JS strings and regexes.
+/// The `<!--` escape also keeps our own marker comments out of the embedded code.
+fn escape_inline_script(js: &str) -> String {
+    escape_end_tag(js, "script").replace("<!--", "<\\!--")
+}
+
+/// Make CSS safe to place inside `<style>...</style>` (`\/` and `\!` are CSS escapes).
+fn escape_inline_style(css: &str) -> String {
+    escape_end_tag(css, "style").replace("<!--", "<\\!--")
+}
 
+/// Replace every case-insensitive `</name` with `<\/name`.
+fn escape_end_tag(text: &str, name: &str) -> String {
+    let needle_len = 2 + name.len();
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find("</") {
+        let candidate = &rest[i..];
+        let is_end_tag = candidate.len() >= needle_len
+            && candidate.as_bytes()[2..needle_len].eq_ignore_ascii_case(name.as_bytes());
+        result.push_str(&rest[..i]);
+        result.push_str(if is_end_tag { "<\\/" } else { "</" });
+        rest = &candidate[2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Patch every target HTML file with the injection its profile calls for.
+///
+/// With `refresh_only` (repair), targets already current are left alone, and so are
+/// foreign ones, with a warning, rather than overwriting someone's edits.
+fn patch_targets(options: PatchOptions, refresh_only: bool) -> PatchResult {
     let targets = find_target_html_files();
+    let mut warnings: Vec<String> = detection::target_rules().1.into_iter().collect();
     if targets.is_empty() {
         return PatchResult {
             success: false,
             message: "No target HTML files found in profile directory.".to_string(),
             files_patched: vec![],
+            profiles: HashMap::new(),
+            warnings,
         };
     }
 
+    let settings = load_settings();
     let mut patched = Vec::new();
+    let mut profiles = HashMap::new();
+    let mut csp_amended = 0;
+    let mut up_to_date = 0;
     for file in &targets {
-        let content = match fs::read_to_string(file) {
-            Ok(c) => c,
-            Err(e) => {
+        let (profile_name, body) = target_injection(&settings, file, options.inject, &mut warnings);
+        profiles.insert(file.to_string_lossy().to_string(), profile_name);
+
+        if refresh_only && let Ok(content) = fs::read_to_string(file) {
+            match block_status(&content, options.inject, body.as_deref()) {
+                PatchStatus::Current => {
+                    up_to_date += 1;
+                    continue;
+                }
+                PatchStatus::Foreign => {
+                    warnings.push(format!(
+                        "{}: injection was edited after install, left as is (uninstall and reinstall to replace it)",
+                        file.display()
+                    ));
+                    continue;
+                }
+                PatchStatus::Unpatched | PatchStatus::Outdated => {}
+            }
+        }
+
+        let injection = body.map(|body| injection_block(options.inject, &body));
+        let checked = match patch_file(file, injection.as_deref(), options.csp) {
+            Ok(checked) => checked,
+            Err(message) => {
                 return PatchResult {
                     success: false,
-                    message: format!("Failed to read {}: {}", file.display(), e),
+                    message,
                     files_patched: patched,
+                    profiles,
+                    warnings,
                 };
             }
         };
+        if checked.amended > 0 {
+            csp_amended += 1;
+        }
+        csp_warnings(file, &checked, options.csp, &mut warnings);
+        if injection.is_some() {
+            patched.push(file.to_string_lossy().to_string());
+        }
+    }
 
-        // If already patched, strip old injection so we re-patch with current settings
-        let content = if is_patched(&content) {
-            strip_injection(&content)
-        } else {
-            content
+    let mut message = if refresh_only {
+        format!("Uprooted repaired. {} files refreshed, {} already current", patched.len(), up_to_date)
+    } else {
+        format!("Uprooted installed. {} files patched", patched.len())
+    };
+    if csp_amended > 0 {
+        message.push_str(&format!(" (CSP amended in {})", csp_amended));
+    }
+    message.push('.');
+    PatchResult {
+        success: true,
+        message,
+        files_patched: patched,
+        profiles,
+        warnings,
+    }
+}
+
+/// Re-patch the targets that are already patched, each keeping its inject mode and CSP
+/// handling, so edited settings reach Root the next time it starts.
+pub fn refresh_settings() -> PatchResult {
+    let settings = load_settings();
+    let mut refreshed = Vec::new();
+    let mut warnings = Vec::new();
+    for file in &find_known_html_files() {
+        let Ok(content) = fs::read_to_string(file) else {
+            continue;
         };
+        let inject = parse_block(&content).and_then(|b| b.inject).unwrap_or_default();
+        let (_, body) = target_injection(&settings, file, inject, &mut warnings);
+        match block_status(&content, inject, body.as_deref()) {
+            PatchStatus::Unpatched | PatchStatus::Current => continue,
+            PatchStatus::Foreign => {
+                warnings.push(format!("{}: injection was edited after install, not refreshed", file.display()));
+                continue;
+            }
+            PatchStatus::Outdated => {}
+        }
 
-        // Backup original
-        let backup_path_str = format!("{}{}", file.to_string_lossy(), BACKUP_SUFFIX);
-        let backup_path = Path::new(&backup_path_str);
-        if !backup_path.exists() {
-            if let Err(e) = fs::copy(file, backup_path) {
+        let csp_mode = if csp::is_amended(&content) { CspMode::Amend } else { CspMode::Report };
+        let injection = body.map(|body| injection_block(inject, &body));
+        match patch_file(file, injection.as_deref(), csp_mode) {
+            Ok(checked) => csp_warnings(file, &checked, csp_mode, &mut warnings),
+            Err(message) => {
                 return PatchResult {
                     success: false,
-                    message: format!("Failed to backup {}: {}", file.display(), e),
-                    files_patched: patched,
+                    message,
+                    files_patched: refreshed,
+                    profiles: HashMap::new(),
+                    warnings,
                 };
             }
         }
+        refreshed.push(file.to_string_lossy().to_string());
+    }
 
-        // Inject before </head>
-        let new_content = content.replace("</head>", &format!("    {}\n  </head>", injection));
-        // Write atomically: write to temp then rename so a crash mid-write cannot corrupt the file
-        let tmp_path = file.with_extension("html.tmp");
-        if let Err(e) = fs::write(&tmp_path, &new_content) {
-            return PatchResult {
-                success: false,
-                message: format!("Failed to write temp file {}: {}", tmp_path.display(), e),
-                files_patched: patched,
-            };
+    PatchResult {
+        success: true,
+        message: format!("{} patched files refreshed.", refreshed.len()),
+        files_patched: refreshed,
+        profiles: HashMap::new(),
+        warnings,
+    }
+}
+
+/// What [`sync_settings`] did to one target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncKind {
+    /// Only the settings line (and the marker hash) was rewritten.
+    SettingsLine,
+    /// More than the settings changed, so the whole block was re-patched.
+    Block,
+}
+
+/// Carry the settings into the patched targets while Root may be running: where only the
+/// settings differ from what is injected, rewrite just the `__UPROOTED_SETTINGS__` line,
+/// otherwise re-patch the block as [`refresh_settings`] does. Current, unpatched and
+/// hand-edited targets are left alone.
+pub fn sync_settings(settings: &UprootedSettings, warnings: &mut Vec<String>) -> Result<Vec<(String, SyncKind)>, String> {
+    let mut synced = Vec::new();
+    for file in &find_known_html_files() {
+        let Ok(content) = fs::read_to_string(file) else {
+            continue;
+        };
+        let inject = parse_block(&content).and_then(|b| b.inject).unwrap_or_default();
+        let (_, body) = target_injection(settings, file, inject, warnings);
+        match block_status(&content, inject, body.as_deref()) {
+            PatchStatus::Unpatched | PatchStatus::Current => continue,
+            PatchStatus::Foreign => {
+                warnings.push(format!("{}: injection was edited after install, not synced", file.display()));
+                continue;
+            }
+            PatchStatus::Outdated => {}
         }
-        if let Err(e) = fs::rename(&tmp_path, file) {
-            let _ = fs::remove_file(&tmp_path);
-            return PatchResult {
-                success: false,
-                message: format!("Failed to finalize write to {}: {}", file.display(), e),
-                files_patched: patched,
-            };
+
+        let csp_mode = if csp::is_amended(&content) { CspMode::Amend } else { CspMode::Report };
+        if let Some(updated) = body.as_deref().and_then(|body| with_settings_line(&content, inject, body)) {
+            write_atomic(file, updated)?;
+            synced.push((file.to_string_lossy().to_string(), SyncKind::SettingsLine));
+            continue;
         }
+        let injection = body.map(|body| injection_block(inject, &body));
+        let checked = patch_file(file, injection.as_deref(), csp_mode)?;
+        csp_warnings(file, &checked, csp_mode, warnings);
+        synced.push((file.to_string_lossy().to_string(), SyncKind::Block));
+    }
+    Ok(synced)
+}
 
-        patched.push(file.to_string_lossy().to_string());
+/// `content` with the settings line of its block replaced by the one in `expected`, when
+/// that is the only difference. The start marker's hash is updated to match, and an amended
+/// CSP is amended again for the new inline script's hash. Everything else keeps its bytes.
+fn with_settings_line(content: &str, inject: InjectMode, expected: &str) -> Option<String> {
+    fn settings_line(body: &str) -> Option<&str> {
+        body.lines().map(str::trim).find(|line| line.starts_with("<script>window.__UPROOTED_SETTINGS__="))
     }
 
-    PatchResult {
-        success: true,
-        message: format!("Uprooted installed. {} files patched.", patched.len()),
-        files_patched: patched,
+    let block = parse_block(content)?;
+    if block.version != env!("CARGO_PKG_VERSION") || block.inject != Some(inject) {
+        return None;
+    }
+    let (old_line, new_line) = (settings_line(block.body)?, settings_line(expected)?);
+    let new_body = block.body.replacen(old_line, new_line, 1);
+    if new_body.trim() != expected.trim() {
+        return None;
+    }
+
+    // The hash and the line both sit inside the block, so `content` splits around them
+    let hash_at = block.hash.as_ptr() as usize - content.as_ptr() as usize;
+    let line_at = block.body.as_ptr() as usize - content.as_ptr() as usize + block.body.find(old_line)?;
+    let updated = format!(
+        "{}{}{}{}{}",
+        &content[..hash_at],
+        payload_hash(&new_body),
+        &content[hash_at + block.hash.len()..line_at],
+        new_line,
+        &content[line_at + old_line.len()..]
+    );
+    let updated = if csp::is_amended(&updated) {
+        let restored = csp::restore(&updated);
+        let block_start = restored.find(MARKER_START)?;
+        let block_end = restored.find(MARKER_END)? + MARKER_END.len();
+        csp::check(&restored, block_start..block_end, CspMode::Amend).html
+    } else {
+        updated
+    };
+
+    // Same guarantee as a full patch: uninstall must still take it back out exactly
+    (strip_injection(&updated) == strip_injection(content)).then_some(updated)
+}
+
+fn csp_warnings(file: &Path, checked: &csp::CspCheck, csp_mode: CspMode, warnings: &mut Vec<String>) {
+    for blocked in &checked.blocked {
+        warnings.push(format!("{}: {}", file.display(), blocked));
+    }
+    if csp_mode == CspMode::Report && !checked.blocked.is_empty() {
+        warnings.push(format!("{}: re-run with --csp amend to allow the injection", file.display()));
+    }
+}
+
+/// Re-patch one file with `injection`; `None` (a profile that injects nothing) only strips it.
+fn patch_file(file: &Path, injection: Option<&str>, csp_mode: CspMode) -> Result<csp::CspCheck, String> {
+    let original = fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+
+    // If already patched, strip old injection so we re-patch with current settings
+    let content = if is_patched(&original) {
+        unpatched_content(file, &original).0
+    } else {
+        original.clone()
+    };
+
+    let Some(injection) = injection else {
+        if content != original {
+            write_atomic(file, &content)?;
+        }
+        return Ok(csp::CspCheck { html: content, blocked: vec![], amended: 0 });
+    };
+
+    // Snapshot the pre-install bytes (a no-op when this exact content is already stored)
+    if !is_patched(&original) {
+        let store = BackupStore::open();
+        store
+            .snapshot(&target_key(file), original.as_bytes())
+            .and_then(|snapshot| store.mark_original(&snapshot))
+            .map_err(|e| format!("Failed to backup {}: {}", file.display(), e))?;
     }
+
+    let new_content = inject(&content, injection)
+        .map_err(|e| format!("Cannot patch {}: {}", file.display(), e))?;
+
+    let block_start = new_content.find(MARKER_START).unwrap_or(0);
+    let block_end = new_content.find(MARKER_END).map_or(new_content.len(), |i| i + MARKER_END.len());
+    let checked = csp::check(&new_content, block_start..block_end, csp_mode);
+
+    // Never write a patch that uninstall couldn't take back out exactly
+    if strip_injection(&checked.html) != content {
+        return Err(format!("Cannot patch {}: the injection would not strip back out cleanly", file.display()));
+    }
+    write_atomic(file, &checked.html)?;
+
+    Ok(checked)
 }
 
 pub fn uninstall() -> PatchResult {
-    let targets = find_target_html_files();
+    let targets = detection::find_installed_html_files();
     let mut restored = Vec::new();
+    let mut warnings = Vec::new();
 
     for file in &targets {
         let content = match fs::read_to_string(file) {
@@ -143,27 +660,47 @@ pub fn uninstall() -> PatchResult {
             continue;
         }
 
-        // Prefer stripping in-place (preserves current Root HTML)
-        let cleaned = strip_injection(&content);
+        // Prefer stripping in-place (preserves current Root HTML), using the exact
+        // pre-install bytes when that's what stripping gives back
+        let (cleaned, verified) = unpatched_content(file, &content);
         if cleaned != content {
-            let _ = fs::write(file, &cleaned);
+            if !verified && BackupStore::open().original(&target_key(file)).is_some() {
+                warnings.push(format!(
+                    "{}: differs from its pre-install snapshot (updated by Root since?), kept as is; `uprooted backups restore` puts back the snapshot",
+                    file.display()
+                ));
+            }
+            if let Err(e) = write_atomic(file, &cleaned) {
+                return PatchResult {
+                    success: false,
+                    message: format!("Failed to restore {}: {}", file.display(), e),
+                    files_patched: restored,
+                    profiles: HashMap::new(),
+                    warnings,
+                };
+            }
             restored.push(file.to_string_lossy().to_string());
 
-            // Clean up backup file if it exists
-            let backup_path_str = format!("{}{}", file.to_string_lossy(), BACKUP_SUFFIX);
+            // Clean up legacy backup file if it exists
+            let backup_path_str = format!("{}{}", file.to_string_lossy(), LEGACY_BACKUP_SUFFIX);
             let _ = fs::remove_file(Path::new(&backup_path_str));
             continue;
         }
 
-        // Fallback: restore from backup if stripping didn't change anything
-        let backup_path_str = format!("{}{}", file.to_string_lossy(), BACKUP_SUFFIX);
+        // Fallback: restore from legacy backup if stripping didn't change anything
+        let backup_path_str = format!("{}{}", file.to_string_lossy(), LEGACY_BACKUP_SUFFIX);
         let backup_path = Path::new(&backup_path_str);
         if backup_path.exists() {
-            if let Err(e) = fs::copy(backup_path, file) {
+            if let Err(e) = fs::read(backup_path)
+                .map_err(|e| format!("Failed to read {}: {}", backup_path.display(), e))
+                .and_then(|data| write_atomic(file, data))
+            {
                 return PatchResult {
                     success: false,
                     message: format!("Failed to restore {}: {}", file.display(), e),
                     files_patched: restored,
+                    profiles: HashMap::new(),
+                    warnings,
                 };
             }
             let _ = fs::remove_file(backup_path);
@@ -178,72 +715,351 @@ pub fn uninstall() -> PatchResult {
             restored.len()
         ),
         files_patched: restored,
+        profiles: HashMap::new(),
+        warnings,
+    }
+}
+
+/// Path of a target relative to the profile directory, `/`-separated: its key in the backup store.
+pub fn target_key(file: &Path) -> String {
+    let profile = get_profile_dir();
+    let relative = file.strip_prefix(&profile).unwrap_or(file);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A patched file's content with the injection removed, and whether that is byte for byte
+/// the recorded pre-install snapshot (checked against its hash).
+///
+/// Files patched by older installers don't always strip back exactly; when the result
+/// matches the snapshot up to whitespace, the snapshot's bytes are returned instead.
+/// Otherwise (Root has changed the file since install) the stripped content is.
+fn unpatched_content(file: &Path, content: &str) -> (String, bool) {
+    let cleaned = strip_injection(content);
+    let Some(original) = BackupStore::open().original(&target_key(file)) else {
+        return (cleaned, false);
+    };
+    if hash::sha256_hex(cleaned.as_bytes()).starts_with(&original.hash) {
+        return (cleaned, true);
+    }
+    let same_lines = |a: &str, b: &str| {
+        a.trim_end().lines().map(str::trim_end).eq(b.trim_end().lines().map(str::trim_end))
+    };
+    if let Ok(bytes) = original.read()
+        && let Ok(text) = String::from_utf8(bytes)
+        && same_lines(&text, &cleaned)
+    {
+        return (text, true);
     }
+    (cleaned, false)
 }
 
-/// Strip injected content between start/end markers, legacy markers, and bare uprooted tags
-/// (from bash installer which historically didn't use markers).
+/// Insert the injection block at the first safe point: before `</head>`, else before `<body>`,
+/// else right after the doctype / `<html>` / `<head>` start tags.
+fn inject(content: &str, injection: &str) -> Result<String, String> {
+    let point = html::find_injection_point(content)?;
+    let (before, after) = content.split_at(point.offset);
+    Ok(format!("{}{}{}{}", before, injection, block_trailer(before), after))
+}
+
+/// What `inject` writes after the block: a line break (in the style of the preceding one)
+/// plus the indentation of the insertion point, so whatever follows keeps its own line and
+/// column. It depends only on the text before the block, so stripping can recompute it and
+/// remove exactly the bytes that were added.
+fn block_trailer(before: &str) -> String {
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line_ending = if before[..line_start].ends_with("\r\n") { "\r\n" } else { "\n" };
+    let line = &before[line_start..];
+    let indent = if line.chars().all(|c| c == ' ' || c == '\t') { line } else { "" };
+    format!("{}{}", line_ending, indent)
+}
+
+/// Remove injections and restore amended CSPs, leaving every other byte as it was.
+///
+/// Versioned blocks are cut out along with exactly the trailer `inject` added. Unversioned
+/// blocks from older installers are removed as the whole lines they occupied, and lines
+/// carrying the dev patcher's legacy marker or a bare tag older bash installers wrote
+/// (matched in full, so a user's own tags that mention uprooted stay) are dropped.
 fn strip_injection(content: &str) -> String {
-    // Detect original line ending style so we don't silently convert CRLF to LF
-    let line_ending = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut content = csp::restore(content);
+    while let Some(start) = content.find(MARKER_START) {
+        let Some(end) = content[start..].find(MARKER_END).map(|i| start + i + MARKER_END.len()) else {
+            break;
+        };
+        let range = if parse_block(&content[start..]).is_some() {
+            let trailer = block_trailer(&content[..start]);
+            start..if content[end..].starts_with(&trailer) { end + trailer.len() } else { end }
+        } else {
+            let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = content[end..].find('\n').map_or(content.len(), |i| end + i + 1);
+            line_start..line_end
+        };
+        content.replace_range(range, "");
+    }
 
-    let mut result = Vec::new();
-    let mut inside_block = false;
+    let bare_tags = legacy_bare_tags();
+    content
+        .split_inclusive('\n')
+        .filter(|line| {
+            let tag = line.trim();
+            !(line.contains(LEGACY_MARKER)
+                || bare_tags.iter().any(|t| t == tag)
+                || (tag.starts_with("<script>window.__UPROOTED_SETTINGS__=") && tag.ends_with("</script>")))
+        })
+        .collect()
+}
 
-    for line in content.lines() {
-        if line.contains(MARKER_START) {
-            inside_block = true;
-            continue;
-        }
-        if line.contains(MARKER_END) {
-            inside_block = false;
-            continue;
-        }
-        if inside_block {
-            continue;
-        }
-        // Legacy: strip lines with old marker
-        if line.contains(LEGACY_MARKER) {
-            continue;
-        }
-        // Bare uprooted tags (bash installer without markers)
-        if line.contains("uprooted-preload")
-            && (line.contains("<script") || line.contains("</script"))
-        {
-            continue;
-        }
-        if line.contains("uprooted.css") && line.contains("<link") {
-            continue;
-        }
-        if line.contains("__UPROOTED_SETTINGS__") && line.contains("<script") {
-            continue;
+/// The script and stylesheet tags older bash installers inserted without markers.
+fn legacy_bare_tags() -> [String; 2] {
+    let uprooted_dir = hook::get_uprooted_dir();
+    let url = |name: &str| format!("file://{}", uprooted_dir.join(name).to_string_lossy());
+    [
+        format!("<script src=\"{}\"></script>", url("uprooted-preload.js")),
+        format!("<link rel=\"stylesheet\" href=\"{}\">", url("uprooted.css")),
+    ]
+}
+
+/// Re-patch only the targets whose injection is missing or outdated.
+pub fn repair(options: PatchOptions) -> PatchResult {
+    patch_targets(options, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::{quickcheck, Arbitrary, Gen, TestResult};
+
+    #[test]
+    fn inline_escapes_end_tags_and_comment_openers() {
+        let js = "const a = '</script>'; const b = '</SCRIPT >'; // <!-- uprooted:end -->\nx </ y";
+        let escaped = escape_inline_script(js);
+        assert!(!escaped.to_ascii_lowercase().contains("</script"));
+        assert!(!escaped.contains("<!--"));
+        assert!(escaped.contains("x </ y"));
+
+        let css = "a::before { content: \"</style><script>\"; }";
+        let escaped = escape_inline_style(css);
+        assert!(!escaped.to_ascii_lowercase().contains("</style"));
+        assert!(escaped.contains("<script>"));
+    }
+
+    #[test]
+    fn integrity_mismatch_is_reported() {
+        let dir = std::env::temp_dir().join(format!("uprooted-integrity-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("uprooted-preload.js");
+        fs::write(&script, "console.log('uprooted');").unwrap();
+
+        let url = file_url(&script);
+        let body = format!(
+            "    <script src=\"{}\" integrity=\"sha384-{}\"></script>\n",
+            url,
+            hash::sha384_base64(b"console.log('uprooted');")
+        );
+        let html = format!("<head>\n{}\n</head>", injection_block(InjectMode::File, &body));
+        assert!(integrity_mismatches(&html).is_empty());
+
+        fs::write(&script, "console.log('tampered');").unwrap();
+        let mismatches = integrity_mismatches(&html);
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].contains("changed since it was patched"), "{mismatches:?}");
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(integrity_mismatches(&html).len(), 1);
+    }
+
+    #[test]
+    fn file_urls_survive_the_html_they_are_written_into() {
+        assert_eq!(file_url(Path::new("C:\\Users\\u\\uprooted.css")), "file:///C:/Users/u/uprooted.css");
+
+        let dir = std::env::temp_dir().join(format!("uprooted-urls-{}", std::process::id())).join("Tom & \"Jerry\"");
+        fs::create_dir_all(&dir).unwrap();
+        let sheet = stylesheets::Stylesheet { name: "compact.css".to_string(), path: dir.join("compact.css") };
+        fs::write(&sheet.path, "a { }").unwrap();
+
+        let tags = stylesheet_tags(InjectMode::File, &[(sheet, "a { }".to_string())]);
+        assert!(tags.contains("Tom &amp; &quot;Jerry&quot;/compact.css"), "{tags}");
+        let html = format!("<head>\n{}\n</head>", injection_block(InjectMode::File, &tags));
+        assert!(integrity_mismatches(&html).is_empty());
+
+        fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn profiles_select_what_is_injected() {
+        let settings = UprootedSettings::default();
+        let theme_only = InjectionProfile { preload: false, ..InjectionProfile::default() };
+        for block in [
+            file_injection(&settings, &theme_only).unwrap(),
+            inline_injection(&settings, &theme_only).unwrap(),
+        ] {
+            assert!(!block.contains("<script"), "{block}");
+            assert!(block.contains("<link") || block.contains("<style"), "{block}");
         }
-        result.push(line);
+
+        let nothing = InjectionProfile { preload: false, theme: false, plugins: None };
+        assert!(file_injection(&settings, &nothing).is_none());
+        assert!(inline_injection(&settings, &nothing).is_none());
     }
 
-    result.join(line_ending)
-}
+    #[test]
+    fn inline_injection_is_detected_and_stripped() {
+        let original = "<!doctype html>\n<html>\n  <head>\n    <title>x</title>\n  </head>\n  <body></body>\n</html>";
+        let body = inline_injection(&UprootedSettings::default(), &InjectionProfile::default()).unwrap();
+        let patched = inject(original, &injection_block(InjectMode::Inline, &body)).unwrap();
+        assert!(is_patched(&patched));
+        assert!(patched.contains("<style data-uprooted-theme>"));
+        assert_eq!(strip_injection(&patched), original);
+    }
 
-pub fn repair() -> PatchResult {
-    let targets = find_target_html_files();
+    #[test]
+    fn status_tracks_version_payload_and_edits() {
+        let original = "<html>\n  <head>\n  </head>\n</html>\n";
+        let body = "    <link rel=\"stylesheet\" href=\"file:///u/uprooted.css\">\n";
+        let patched = inject(original, &injection_block(InjectMode::File, body)).unwrap();
+        assert!(patched.contains(&format!("<!-- uprooted:start v{} file sha256-", env!("CARGO_PKG_VERSION"))));
 
-    // Strip existing injection in-place (preserves Root's current HTML)
-    for file in &targets {
-        let content = match fs::read_to_string(file) {
-            Ok(c) => c,
-            Err(_) => continue,
+        assert_eq!(block_status(original, InjectMode::File, Some(body)), PatchStatus::Unpatched);
+        assert_eq!(block_status(&patched, InjectMode::File, Some(body)), PatchStatus::Current);
+        // Settings or paths changed since, a different mode, or a profile that now injects nothing
+        assert_eq!(block_status(&patched, InjectMode::File, Some("<link>")), PatchStatus::Outdated);
+        assert_eq!(block_status(&patched, InjectMode::Inline, Some(body)), PatchStatus::Outdated);
+        assert_eq!(block_status(&patched, InjectMode::File, None), PatchStatus::Outdated);
+
+        let older = patched.replace(&format!(" v{} ", env!("CARGO_PKG_VERSION")), " v0.3.0 ");
+        assert_eq!(block_status(&older, InjectMode::File, Some(body)), PatchStatus::Outdated);
+        let unversioned = "<head>\n<!-- uprooted:start -->\n<link href=\"uprooted.css\">\n<!-- uprooted:end -->\n</head>";
+        assert_eq!(block_status(unversioned, InjectMode::File, Some(body)), PatchStatus::Outdated);
+
+        let edited = patched.replace("uprooted.css", "custom.css");
+        assert_eq!(block_status(&edited, InjectMode::File, Some(body)), PatchStatus::Foreign);
+        assert_eq!(strip_injection(&edited), original);
+    }
+
+    #[test]
+    fn settings_line_is_rewritten_in_place() {
+        let original = "<html>\n  <head>\n    <meta http-equiv=\"Content-Security-Policy\" content=\"script-src 'self'\">\n  </head>\n</html>\n";
+        let profile = InjectionProfile::default();
+        let before = file_injection(&UprootedSettings::default(), &profile).unwrap();
+        let patched = inject(original, &injection_block(InjectMode::File, &before)).unwrap();
+        let block = patched.find(MARKER_START).unwrap()..patched.find(MARKER_END).unwrap() + MARKER_END.len();
+        let patched = csp::check(&patched, block, CspMode::Amend).html;
+
+        let settings = UprootedSettings { custom_css: "</style> body { color: red; }".to_string(), ..Default::default() };
+        let after = file_injection(&settings, &profile).unwrap();
+        let synced = with_settings_line(&patched, InjectMode::File, &after).unwrap();
+        assert_eq!(block_status(&synced, InjectMode::File, Some(&after)), PatchStatus::Current);
+        assert!(synced.contains(&settings_script_json(&settings)));
+        assert_eq!(strip_injection(&synced), original);
+        // The amended policy now allows the new settings script instead of the old one
+        let script = |body: &str| body.lines().find(|l| l.contains("__UPROOTED_SETTINGS__")).unwrap().trim().to_string();
+        let script_hash = |body: &str| {
+            let text = script(body).trim_start_matches("<script>").trim_end_matches("</script>").to_string();
+            hash::sha256_base64(text.as_bytes())
         };
+        assert!(synced.contains(&script_hash(&after)) && !synced.contains(&script_hash(&before)));
+
+        // Anything besides the settings line needs the whole block
+        let theme_only = InjectionProfile { preload: false, ..InjectionProfile::default() };
+        assert!(with_settings_line(&patched, InjectMode::File, &file_injection(&settings, &theme_only).unwrap()).is_none());
+        assert!(with_settings_line(&patched, InjectMode::Inline, &after).is_none());
+    }
 
-        if is_patched(&content) {
-            let cleaned = strip_injection(&content);
-            let _ = fs::write(file, &cleaned);
+    /// A document built from HTML fragments, mixed line endings and arbitrary text.
+    #[derive(Clone, Debug)]
+    struct Document(String);
 
-            // Update backup to current clean state
-            let backup_path_str = format!("{}{}", file.to_string_lossy(), BACKUP_SUFFIX);
-            let _ = fs::write(Path::new(&backup_path_str), &cleaned);
+    impl Arbitrary for Document {
+        fn arbitrary(g: &mut Gen) -> Self {
+            const FRAGMENTS: &[&str] = &[
+                "<!doctype html>", "<html>", "<HEAD>", "<head>", "</head>", "<body>", "</body>", "</html>",
+                "\n", "\r\n", "\r", "  ", "\t", "<title>x</title>", "<!-- </head> -->",
+                "<script>if (a </b) {}</script>", "<style>p{}</style>",
+                "<meta http-equiv=\"Content-Security-Policy\" content=\"default-src 'self'; script-src 'nonce-r00t'\">",
+                "<meta http-equiv=Content-Security-Policy content=\"style-src 'none'\">",
+                "<link rel=\"stylesheet\" href=\"uprooted.css\">",
+                "<script src=\"./uprooted-preload.js\"></script>",
+            ];
+            let len = usize::arbitrary(g) % 24;
+            let mut html = String::new();
+            for _ in 0..len {
+                if bool::arbitrary(g) {
+                    html.push_str(g.choose(FRAGMENTS).unwrap());
+                } else {
+                    html.push_str(&String::arbitrary(g));
+                }
+            }
+            Document(html)
+        }
+    }
+
+    #[test]
+    fn install_then_uninstall_restores_every_byte() {
+        fn round_trip(doc: Document, inline: bool, amend: bool) -> TestResult {
+            let original = doc.0;
+            if original.contains(MARKER_START) || original.contains(MARKER_END) {
+                return TestResult::discard();
+            }
+            let (mode, body) = if inline {
+                (InjectMode::Inline, inline_injection(&UprootedSettings::default(), &InjectionProfile::default()))
+            } else {
+                (InjectMode::File, file_injection(&UprootedSettings::default(), &InjectionProfile::default()))
+            };
+            let Ok(patched) = inject(&original, &injection_block(mode, &body.unwrap())) else {
+                return TestResult::discard();
+            };
+            let block = patched.find(MARKER_START).unwrap()..patched.find(MARKER_END).unwrap() + MARKER_END.len();
+            let checked = csp::check(&patched, block, if amend { CspMode::Amend } else { CspMode::Report });
+
+            let stripped = strip_injection(&checked.html);
+            TestResult::from_bool(
+                stripped == original
+                    && hash::sha256_hex(stripped.as_bytes()) == hash::sha256_hex(original.as_bytes())
+                    && strip_injection(&original) == original,
+            )
         }
+        quickcheck(round_trip as fn(Document, bool, bool) -> TestResult);
+    }
+
+    #[test]
+    fn strip_keeps_user_lines_and_line_endings() {
+        let original = "<html>\r\n<head>\n  <link rel=\"stylesheet\" href=\"uprooted.css\">\r\n  </head>\n\n";
+        let patched = inject(original, &injection_block(InjectMode::File, "    <script></script>\n")).unwrap();
+        assert!(patched.contains("  <!-- uprooted:start v"));
+        assert!(patched.contains("<!-- uprooted:end -->\r\n  </head>\n\n"));
+        assert_eq!(strip_injection(&patched), original);
+
+        // Layout of the unversioned blocks older installers wrote before </head>
+        let legacy = "<head>\n      <!-- uprooted:start -->\n    <script src=\"x\"></script>\n    <!-- uprooted:end -->\n  </head>\n";
+        assert_eq!(strip_injection(legacy), "<head>\n  </head>\n");
     }
 
-    // Re-install fresh patches
-    install()
+    #[test]
+    fn status_ignores_the_order_settings_were_loaded_in() {
+        // Each load builds new `HashMap`s, which iterate in a different order
+        let load = || -> UprootedSettings {
+            serde_json::from_str(
+                r##"{"enabled": true, "customCss": "", "plugins": {
+                    "sentry-blocker": {"enabled": true, "config": {}},
+                    "themes": {"enabled": true, "config": {"theme": "custom", "customAccent": "#3B6AF8", "customBackground": "#0D1521"}},
+                    "settings-panel": {"enabled": true, "config": {}},
+                    "link-embeds": {"enabled": true, "config": {"youtube": true, "websites": false, "maxEmbedsPerMessage": 3}},
+                    "silent-typing": {"enabled": false, "config": {}},
+                    "nsfw-filter": {"enabled": false, "config": {}}
+                }}"##,
+            )
+            .unwrap()
+        };
+        let original = "<html>\n  <head>\n  </head>\n</html>\n";
+        let profile = InjectionProfile::default();
+        let body = file_injection(&load(), &profile).unwrap();
+        let patched = inject(original, &injection_block(InjectMode::File, &body)).unwrap();
+        for _ in 0..8 {
+            let expected = file_injection(&load(), &profile).unwrap();
+            assert_eq!(block_status(&patched, InjectMode::File, Some(&expected)), PatchStatus::Current);
+        }
+    }
 }
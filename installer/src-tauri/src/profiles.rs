@@ -0,0 +1,106 @@
+//! Named settings profiles (`uprooted profile save / switch / list / delete`).
+//!
+//! Whole copies of `uprooted-settings.json`, kept as `uprooted-profiles/<name>.json`
+//! next to it in the same format, so they can be edited like the settings file. Resetting
+//! settings (and so uninstalling) deletes them along with it. Not to be confused with
+//! injection profiles, which choose what each target gets injected.
+
+use crate::fsutil::write_atomic;
+use crate::settings::{check_settings, settings_path, UprootedSettings};
+use std::fs;
+use std::path::PathBuf;
+
+pub fn profiles_dir() -> PathBuf {
+    settings_path().with_file_name("uprooted-profiles")
+}
+
+/// Whether `name` can name a profile: lowercase letters, digits, `-` and `_`.
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn profile_path(name: &str) -> Result<PathBuf, String> {
+    if !valid_name(name) {
+        return Err(format!("Invalid profile name {:?}: use lowercase letters, digits, '-' and '_'", name));
+    }
+    Ok(profiles_dir().join(format!("{}.json", name)))
+}
+
+/// Saved profile names, sorted.
+pub fn list() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(profiles_dir()) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.path().file_name()?.to_str()?.strip_suffix(".json").map(str::to_string))
+        .filter(|name| valid_name(name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// A saved profile, migrated and validated like the settings file.
+pub fn load(name: &str) -> Result<UprootedSettings, String> {
+    let path = profile_path(name)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|_| format!("No profile {} (see `uprooted profile list`)", name))?;
+    match check_settings(&content) {
+        (Some(settings), _) => Ok(settings),
+        (None, report) => Err(format!("Profile {} is not valid: {}", name, report.errors.join("; "))),
+    }
+}
+
+/// Save `settings` as profile `name`; returns whether it replaced an existing profile.
+pub fn save(name: &str, settings: &UprootedSettings) -> Result<bool, String> {
+    let path = profile_path(name)?;
+    let dir = profiles_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let json = serde_json::to_string_pretty(settings).map_err(|e| format!("Failed to serialize: {}", e))?;
+    let replaced = path.exists();
+    write_atomic(&path, json)?;
+    Ok(replaced)
+}
+
+pub fn delete(name: &str) -> Result<(), String> {
+    let path = profile_path(name)?;
+    if !path.exists() {
+        return Err(format!("No profile {} (see `uprooted profile list`)", name));
+    }
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
+
+/// Whether two settings are the same, whatever order their maps are in.
+pub fn same(a: &UprootedSettings, b: &UprootedSettings) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_stay_inside_the_profiles_dir() {
+        assert!(valid_name("streaming") && valid_name("daily_2"));
+        for name in ["", "Daily", "../settings", "a.b", "a/b", "a b"] {
+            assert!(!valid_name(name), "{name}");
+            assert!(profile_path(name).is_err(), "{name}");
+        }
+        assert_eq!(profile_path("daily").unwrap(), profiles_dir().join("daily.json"));
+    }
+
+    #[test]
+    fn settings_compare_by_content() {
+        let streaming: UprootedSettings = serde_json::from_str(
+            r#"{"enabled": true, "customCss": "", "plugins": {"nsfw-filter": {"enabled": true, "config": {}}, "link-embeds": {"enabled": false, "config": {}}}}"#,
+        )
+        .unwrap();
+        let reordered: UprootedSettings = serde_json::from_str(
+            r#"{"enabled": true, "customCss": "", "plugins": {"link-embeds": {"enabled": false, "config": {}}, "nsfw-filter": {"enabled": true, "config": {}}}}"#,
+        )
+        .unwrap();
+        assert!(same(&streaming, &reordered));
+        assert!(!same(&streaming, &UprootedSettings::default()));
+    }
+}
@@ -0,0 +1,186 @@
+//! Quoting for values embedded in generated launchers and config files.
+//!
+//! Install directories and AppImage paths are user-controlled, so every path written
+//! into a shell script, `.desktop` entry or `environment.d` file goes through here.
+
+/// Quote a value as a single POSIX shell word.
+///
+/// Single quotes disable every expansion; an embedded `'` closes the quote,
+/// emits an escaped quote and reopens it.
+pub fn shell(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Quote a value as a single argument of a desktop entry `Exec` key.
+///
+/// Applies the Exec quoting rules (double quotes, backslash before `"` `` ` `` `$` `\`),
+/// doubles `%` so it isn't read as a field code, then applies the general string
+/// escapes, which is why a literal backslash ends up as four backslashes.
+#[cfg(target_os = "linux")]
+pub fn desktop_exec(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    desktop_string(&quoted)
+}
+
+/// Escape a value of desktop entry type `string` (`\s`, `\n`, `\t`, `\r`, `\\`).
+#[cfg(target_os = "linux")]
+pub fn desktop_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '\n' => escaped.push_str(r"\n"),
+            '\t' => escaped.push_str(r"\t"),
+            '\r' => escaped.push_str(r"\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quote a value for a systemd `environment.d` assignment (`KEY=<value>`).
+///
+/// Double quotes keep whitespace and `#`; `\`, `"`, `` ` `` and `$` are
+/// backslash-escaped so they are neither unquoted nor expanded.
+#[cfg(target_os = "linux")]
+pub fn environment_d(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTILE: &[&str] = &[
+        "/home/user/Applications/Root.AppImage",
+        "/home/my user/Root App/Root.AppImage",
+        "/home/o'brien/it's \"quoted\"/Root",
+        "/tmp/100%/%f %u %%/Root",
+        "/tmp/$HOME/${PATH}/`id`/$(id)/Root",
+        r"/tmp/back\slash\\double/Root",
+        "/tmp/new\nline/tab\there/Root",
+        "/tmp/semi;colon|pipe&amp>gt<lt*star?q#hash~tilde/Root",
+        "",
+    ];
+
+    #[test]
+    fn shell_round_trips_through_sh() {
+        for value in HOSTILE {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf '%s' {}", shell(value)))
+                .output()
+                .expect("sh should be available");
+            assert_eq!(String::from_utf8_lossy(&output.stdout), *value);
+        }
+    }
+
+    #[test]
+    fn shell_quotes_single_quotes() {
+        assert_eq!(shell("it's"), r"'it'\''s'");
+        assert_eq!(shell(""), "''");
+    }
+
+    /// Reverse of `desktop_exec`, following the spec's unescape order.
+    #[cfg(target_os = "linux")]
+    fn parse_desktop_exec_arg(escaped: &str) -> String {
+        // General string escapes first
+        let mut string = String::new();
+        let mut chars = escaped.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                string.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('s') => string.push(' '),
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some('r') => string.push('\r'),
+                Some('\\') => string.push('\\'),
+                other => panic!("invalid string escape: {other:?}"),
+            }
+        }
+
+        // Then Exec argument quoting and field codes
+        let inner = string
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .expect("argument should be double-quoted");
+        let mut arg = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => arg.push(chars.next().expect("dangling backslash")),
+                '%' => {
+                    assert_eq!(chars.next(), Some('%'), "unescaped field code in {escaped}");
+                    arg.push('%');
+                }
+                '"' | '`' | '$' => panic!("unescaped {c} in {escaped}"),
+                _ => arg.push(c),
+            }
+        }
+        arg
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn desktop_exec_round_trips() {
+        for value in HOSTILE {
+            let escaped = desktop_exec(value);
+            assert!(!escaped.contains('\n'), "newline leaked into {escaped:?}");
+            assert_eq!(parse_desktop_exec_arg(&escaped), *value);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn desktop_exec_escapes_backslash_and_percent() {
+        assert_eq!(desktop_exec(r"a\b"), r#""a\\\\b""#);
+        assert_eq!(desktop_exec("100%"), "\"100%%\"");
+        assert_eq!(desktop_exec("a b"), "\"a b\"");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn environment_d_escapes_specials() {
+        assert_eq!(environment_d("/home/my user/x"), "\"/home/my user/x\"");
+        assert_eq!(environment_d("$HOME"), r#""\$HOME""#);
+        assert_eq!(environment_d(r#"a"b\c`d"#), r#""a\"b\\c\`d""#);
+        for value in HOSTILE {
+            let quoted = environment_d(value);
+            let inner = &quoted[1..quoted.len() - 1];
+            let mut unescaped = String::new();
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unescaped.push(chars.next().expect("dangling backslash")),
+                    '"' | '`' | '$' => panic!("unescaped {c} in {quoted}"),
+                    _ => unescaped.push(c),
+                }
+            }
+            assert_eq!(unescaped, *value);
+        }
+    }
+}
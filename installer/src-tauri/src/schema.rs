@@ -0,0 +1,301 @@
+//! Versioned schema for `uprooted-settings.json`.
+//!
+//! The file carries a `version`; files from before it existed are version 0. Loading runs
+//! every migration from the file's version up to [`SETTINGS_VERSION`], then checks the
+//! result against the schema, so problems are reported with the JSON path they occur at
+//! instead of turning into an all-defaults load.
+
+use serde_json::{Map, Value};
+
+/// Version written by this installer.
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// Migration from version `n` to `n + 1` is `MIGRATIONS[n]`.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[fill_defaults];
+
+/// v0 -> v1: the preload merged a file over its defaults, so keys could be left out.
+/// Fill them in the way it did, including per-plugin `enabled` and `config`.
+fn fill_defaults(settings: &mut Map<String, Value>) {
+    settings.entry("enabled").or_insert(Value::Bool(true));
+    settings.entry("customCss").or_insert_with(|| Value::String(String::new()));
+    let plugins = settings.entry("plugins").or_insert_with(|| Value::Object(Map::new()));
+    if let Value::Object(plugins) = plugins {
+        for plugin in plugins.values_mut() {
+            if let Value::Object(plugin) = plugin {
+                plugin.entry("enabled").or_insert(Value::Bool(false));
+                plugin.entry("config").or_insert_with(|| Value::Object(Map::new()));
+            }
+        }
+    }
+}
+
+/// One problem found by [`validate`].
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    /// Where it is, e.g. `plugins.themes.enabled` or `injectionTargets[0].profile`.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", if self.path.is_empty() { "(root)" } else { &self.path }, self.message)
+    }
+}
+
+/// Version recorded in `value`, 0 when absent.
+pub fn version(value: &Value) -> Result<u32, String> {
+    match value.get("version") {
+        None => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("version: expected a non-negative integer, found {}", v)),
+    }
+}
+
+/// Bring `value` up to [`SETTINGS_VERSION`], returning the version it was at. Files from
+/// a newer installer are left as they are.
+pub fn migrate(value: &mut Value) -> Result<u32, String> {
+    let from = version(value)?;
+    let Value::Object(settings) = value else {
+        return Err(format!("(root): expected an object, found {}", kind(value)));
+    };
+    if from >= SETTINGS_VERSION {
+        return Ok(from);
+    }
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(settings);
+    }
+    settings.insert("version".to_string(), Value::from(SETTINGS_VERSION));
+    Ok(from)
+}
+
+/// Check migrated settings against the schema.
+///
+/// Errors make the file unusable; warnings (unknown keys, usually typos the preload would
+/// silently ignore) don't.
+pub fn validate(value: &Value) -> (Vec<Issue>, Vec<Issue>) {
+    let mut v = Validator::default();
+    let Some(root) = v.object(value, "") else {
+        return (v.errors, v.warnings);
+    };
+    v.unknown_keys(
+        root,
+        "",
+        &["version", "enabled", "plugins", "customCss", "injectionProfiles", "injectionTargets", "stylesheetInjection"],
+    );
+    if let Ok(found) = version(value)
+        && found > SETTINGS_VERSION
+    {
+        v.warn("version", format!("written by a newer Uprooted (version {}, this installer knows {})", found, SETTINGS_VERSION));
+    }
+    v.required(root, "", "enabled", Validator::bool);
+    v.required(root, "", "customCss", Validator::string);
+
+    if let Some(plugins) = v.required(root, "", "plugins", Validator::object) {
+        for (name, plugin) in plugins {
+            let path = join("plugins", name);
+            let Some(plugin) = v.object(plugin, &path) else {
+                continue;
+            };
+            v.unknown_keys(plugin, &path, &["enabled", "config"]);
+            v.required(plugin, &path, "enabled", Validator::bool);
+            v.required(plugin, &path, "config", Validator::object);
+        }
+    }
+
+    if let Some(profiles) = root.get("injectionProfiles").and_then(|p| v.object(p, "injectionProfiles")) {
+        for (name, profile) in profiles {
+            let path = join("injectionProfiles", name);
+            let Some(profile) = v.object(profile, &path) else {
+                continue;
+            };
+            v.unknown_keys(profile, &path, &["preload", "theme", "plugins"]);
+            for key in ["preload", "theme"] {
+                if let Some(flag) = profile.get(key) {
+                    v.bool(flag, &join(&path, key));
+                }
+            }
+            if let Some(allowed) = profile.get("plugins") {
+                let path = join(&path, "plugins");
+                for (i, name) in v.array(allowed, &path).into_iter().flatten().enumerate() {
+                    v.string(name, &format!("{}[{}]", path, i));
+                }
+            }
+        }
+    }
+
+    if let Some(targets) = root.get("injectionTargets") {
+        for (i, target) in v.array(targets, "injectionTargets").into_iter().flatten().enumerate() {
+            let path = format!("injectionTargets[{}]", i);
+            let Some(target) = v.object(target, &path) else {
+                continue;
+            };
+            v.unknown_keys(target, &path, &["target", "profile"]);
+            if let Some(glob) = v.required(target, &path, "target", Validator::string)
+                && let Err(e) = glob::Pattern::new(glob)
+            {
+                v.error(&join(&path, "target"), format!("invalid glob: {}", e));
+            }
+            v.required(target, &path, "profile", Validator::string);
+        }
+    }
+
+    if let Some(mode) = root.get("stylesheetInjection")
+        && let Some(name) = v.string(mode, "stylesheetInjection")
+        && !["custom-css", "link"].contains(&name)
+    {
+        v.error("stylesheetInjection", format!("expected \"custom-css\" or \"link\", found {:?}", name));
+    }
+
+    (v.errors, v.warnings)
+}
+
+#[derive(Default)]
+struct Validator {
+    errors: Vec<Issue>,
+    warnings: Vec<Issue>,
+}
+
+impl Validator {
+    fn error(&mut self, path: &str, message: String) {
+        self.errors.push(Issue { path: path.to_string(), message });
+    }
+
+    fn warn(&mut self, path: &str, message: String) {
+        self.warnings.push(Issue { path: path.to_string(), message });
+    }
+
+    fn expected(&mut self, path: &str, what: &str, value: &Value) {
+        self.error(path, format!("expected {}, found {}", what, kind(value)));
+    }
+
+    fn object<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a Map<String, Value>> {
+        let object = value.as_object();
+        if object.is_none() {
+            self.expected(path, "an object", value);
+        }
+        object
+    }
+
+    fn array<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a Vec<Value>> {
+        let array = value.as_array();
+        if array.is_none() {
+            self.expected(path, "an array", value);
+        }
+        array
+    }
+
+    fn string<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a str> {
+        let string = value.as_str();
+        if string.is_none() {
+            self.expected(path, "a string", value);
+        }
+        string
+    }
+
+    fn bool<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a bool> {
+        let Value::Bool(flag) = value else {
+            self.expected(path, "true or false", value);
+            return None;
+        };
+        Some(flag)
+    }
+
+    /// Check `parent[key]` with `check`, reporting it if missing.
+    fn required<'a, T: ?Sized>(
+        &mut self,
+        parent: &'a Map<String, Value>,
+        path: &str,
+        key: &str,
+        check: fn(&mut Self, &'a Value, &str) -> Option<&'a T>,
+    ) -> Option<&'a T> {
+        let path = join(path, key);
+        match parent.get(key) {
+            Some(value) => check(self, value, &path),
+            None => {
+                self.error(&path, "missing".to_string());
+                None
+            }
+        }
+    }
+
+    fn unknown_keys(&mut self, object: &Map<String, Value>, path: &str, known: &[&str]) {
+        for key in object.keys().filter(|k| !known.contains(&k.as_str())) {
+            self.warn(&join(path, key), "unknown key, ignored".to_string());
+        }
+    }
+}
+
+/// Append `key` to a path, quoting keys that a dotted path couldn't express.
+pub fn join(path: &str, key: &str) -> String {
+    let plain = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match (path.is_empty(), plain) {
+        (true, true) => key.to_string(),
+        (false, true) => format!("{}.{}", path, key),
+        (_, false) => format!("{}[{}]", path, Value::from(key)),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn v0_files_are_migrated() {
+        let mut value = json!({"plugins": {"themes": {"config": {"theme": "crimson"}}}});
+        assert_eq!(migrate(&mut value).unwrap(), 0);
+        assert_eq!(
+            value,
+            json!({
+                "version": SETTINGS_VERSION,
+                "enabled": true,
+                "customCss": "",
+                "plugins": {"themes": {"enabled": false, "config": {"theme": "crimson"}}}
+            })
+        );
+        assert_eq!(validate(&value), (vec![], vec![]));
+        assert_eq!(migrate(&mut value).unwrap(), SETTINGS_VERSION);
+
+        assert!(migrate(&mut json!([])).unwrap_err().contains("expected an object"));
+        assert!(migrate(&mut json!({"version": "2"})).unwrap_err().contains("version"));
+    }
+
+    #[test]
+    fn problems_are_reported_with_their_path() {
+        let mut value = json!({
+            "enabled": "yes",
+            "plugins": {"link-embeds": {"enabeld": true, "config": {}}, "odd.name": 3},
+            "customCss": "",
+            "injectionTargets": [{"target": "[", "profile": 1}],
+            "colour": "red"
+        });
+        migrate(&mut value).unwrap();
+        let (errors, warnings) = validate(&value);
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "enabled: expected true or false, found a string",
+                "plugins[\"odd.name\"]: expected an object, found a number",
+                "injectionTargets[0].target: invalid glob: Pattern syntax error near position 0: invalid range pattern",
+                "injectionTargets[0].profile: expected a string, found a number",
+            ]
+        );
+        let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, ["colour: unknown key, ignored", "plugins.link-embeds.enabeld: unknown key, ignored"]);
+    }
+}
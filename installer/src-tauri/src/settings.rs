@@ -1,49 +1,320 @@
 use crate::detection::get_profile_dir;
+use crate::fsutil::write_atomic;
+use crate::schema;
+pub use crate::schema::SETTINGS_VERSION;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Profile used for targets no `injectionTargets` entry matches.
+pub const FULL_PROFILE: &str = "full";
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct PluginSettings {
     pub enabled: bool,
     pub config: HashMap<String, serde_json::Value>,
 }
 
+/// What the patcher injects into a target.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct InjectionProfile {
+    /// The settings blob and preload script (plus plugin scripts in inline mode).
+    pub preload: bool,
+    /// The theme stylesheet.
+    pub theme: bool,
+    /// Plugins allowed to run on the target; `None` allows every enabled plugin.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<String>>,
+}
+
+impl Default for InjectionProfile {
+    fn default() -> Self {
+        Self {
+            preload: true,
+            theme: true,
+            plugins: None,
+        }
+    }
+}
+
+/// Where stylesheets added with `uprooted css` are injected.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StylesheetInjection {
+    /// Compiled into a region at the end of `customCss`.
+    #[default]
+    CustomCss,
+    /// A `<link>` (inline mode: `<style>`) per stylesheet, after the theme.
+    Link,
+}
+
+impl StylesheetInjection {
+    fn is_default(&self) -> bool {
+        *self == StylesheetInjection::default()
+    }
+}
+
+/// Assigns the profile `profile` to targets matching the glob `target`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InjectionTarget {
+    /// Glob relative to the profile directory, e.g. `RootApps/*/index.html`.
+    pub target: String,
+    pub profile: String,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct UprootedSettings {
+    /// Schema version, see [`schema`](crate::schema).
+    #[serde(default)]
+    pub version: u32,
     pub enabled: bool,
     pub plugins: HashMap<String, PluginSettings>,
     pub custom_css: String,
+    /// Named injection profiles. Installer-only: stripped from the injected settings.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub injection_profiles: HashMap<String, InjectionProfile>,
+    /// Profile per target glob, first match wins; unmatched targets use [`FULL_PROFILE`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub injection_targets: Vec<InjectionTarget>,
+    /// See [`stylesheets`](crate::stylesheets). Installer-only.
+    #[serde(default, skip_serializing_if = "StylesheetInjection::is_default")]
+    pub stylesheet_injection: StylesheetInjection,
 }
 
 impl Default for UprootedSettings {
     fn default() -> Self {
         Self {
+            version: SETTINGS_VERSION,
             enabled: true,
             plugins: HashMap::new(),
             custom_css: String::new(),
+            injection_profiles: HashMap::new(),
+            injection_targets: Vec::new(),
+            stylesheet_injection: StylesheetInjection::default(),
         }
     }
 }
 
-fn settings_path() -> std::path::PathBuf {
+impl UprootedSettings {
+    /// Name and profile for the target at `relative` (relative to the profile directory).
+    ///
+    /// [`FULL_PROFILE`] is built in (preload, theme, all plugins) unless redefined.
+    pub fn injection_profile(&self, relative: &Path) -> Result<(String, InjectionProfile), String> {
+        let mut name = FULL_PROFILE;
+        for rule in &self.injection_targets {
+            let pattern = glob::Pattern::new(&rule.target)
+                .map_err(|e| format!("invalid injection target {:?}: {}", rule.target, e))?;
+            if pattern.matches_path(relative) {
+                name = &rule.profile;
+                break;
+            }
+        }
+        match self.injection_profiles.get(name) {
+            Some(profile) => Ok((name.to_string(), profile.clone())),
+            None if name == FULL_PROFILE => Ok((name.to_string(), InjectionProfile::default())),
+            None => Err(format!("unknown injection profile {:?}", name)),
+        }
+    }
+
+    /// The settings injected into a target using `profile`: installer-only fields removed
+    /// and plugins the profile doesn't allow disabled.
+    pub fn for_profile(&self, profile: &InjectionProfile) -> UprootedSettings {
+        let mut settings = self.clone();
+        settings.injection_profiles.clear();
+        settings.injection_targets.clear();
+        settings.stylesheet_injection = StylesheetInjection::default();
+        if let Some(allowed) = &profile.plugins {
+            for (name, plugin) in settings.plugins.iter_mut() {
+                if !allowed.contains(name) {
+                    plugin.enabled = false;
+                }
+            }
+        }
+        settings
+    }
+
+    /// Value at a dotted path such as `plugins.link-embeds.enabled`.
+    pub fn get(&self, path: &str) -> Result<Option<Value>, String> {
+        let keys = parse_path(path)?;
+        let mut value = &self.to_value();
+        for key in keys {
+            match value.get(key) {
+                Some(next) => value = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(value.clone()))
+    }
+
+    /// Set the value at a dotted path, creating missing objects on the way; a new plugin
+    /// entry starts out disabled with no config.
+    pub fn set(&mut self, path: &str, new_value: Value) -> Result<(), String> {
+        let keys = parse_path(path)?;
+        let mut root = self.to_value();
+        let mut value = &mut root;
+        for (depth, key) in keys[..keys.len() - 1].iter().enumerate() {
+            let Value::Object(map) = value else {
+                return Err(format!("{} is not an object", keys[..depth].join(".")));
+            };
+            value = map.entry(key.to_string()).or_insert_with(|| {
+                if depth == 1 && keys[0] == "plugins" {
+                    serde_json::to_value(PluginSettings::default()).unwrap_or_default()
+                } else {
+                    Value::Object(Default::default())
+                }
+            });
+        }
+        let Value::Object(map) = value else {
+            return Err(format!("{} is not an object", keys[..keys.len() - 1].join(".")));
+        };
+        map.insert(keys[keys.len() - 1].to_string(), new_value);
+        *self = serde_json::from_value(root).map_err(|e| format!("Invalid value for {}: {}", path, e))?;
+        Ok(())
+    }
+
+    /// Remove the value at a dotted path; `false` if there was none.
+    pub fn unset(&mut self, path: &str) -> Result<bool, String> {
+        let keys = parse_path(path)?;
+        let mut root = self.to_value();
+        let mut value = &mut root;
+        for key in &keys[..keys.len() - 1] {
+            match value.get_mut(*key) {
+                Some(next) => value = next,
+                None => return Ok(false),
+            }
+        }
+        let removed = value
+            .as_object_mut()
+            .and_then(|map| map.remove(keys[keys.len() - 1]))
+            .is_some();
+        if removed {
+            *self = serde_json::from_value(root).map_err(|e| format!("Cannot unset {}: {}", path, e))?;
+        }
+        Ok(removed)
+    }
+
+    fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<&str>, String> {
+    let keys: Vec<&str> = path.split('.').collect();
+    if keys.iter().any(|k| k.is_empty()) {
+        return Err(format!("Invalid settings path {:?} (expected e.g. plugins.themes.enabled)", path));
+    }
+    Ok(keys)
+}
+
+pub fn settings_path() -> PathBuf {
     get_profile_dir().join("uprooted-settings.json")
 }
 
-pub fn load_settings() -> UprootedSettings {
-    let path = settings_path();
-    if path.exists() {
-        if let Ok(content) = fs::read_to_string(&path) {
-            if let Ok(settings) = serde_json::from_str::<UprootedSettings>(&content) {
-                return settings;
-            }
+/// Prefix of the names unusable settings files are moved to.
+const QUARANTINE_PREFIX: &str = "uprooted-settings.json.invalid-";
+
+/// What [`check_settings`] found in a settings file.
+pub struct SettingsReport {
+    /// Schema version the file was written with, once it parses.
+    pub version: Option<u32>,
+    /// Problems that make the file unusable.
+    pub errors: Vec<String>,
+    /// Problems that don't, e.g. unknown keys.
+    pub warnings: Vec<String>,
+}
+
+/// Parse settings JSON, migrate it to the current schema and validate it.
+///
+/// Returns the settings unless there are errors.
+pub fn check_settings(content: &str) -> (Option<UprootedSettings>, SettingsReport) {
+    let mut report = SettingsReport { version: None, errors: vec![], warnings: vec![] };
+    let mut value: Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(e) => {
+            report.errors.push(format!("invalid JSON: {}", e));
+            return (None, report);
+        }
+    };
+    match schema::migrate(&mut value) {
+        Ok(version) => report.version = Some(version),
+        Err(e) => {
+            report.errors.push(e);
+            return (None, report);
+        }
+    }
+    let (errors, warnings) = schema::validate(&value);
+    report.errors.extend(errors.iter().map(ToString::to_string));
+    report.warnings.extend(warnings.iter().map(ToString::to_string));
+    if !report.errors.is_empty() {
+        return (None, report);
+    }
+    match serde_json::from_value(value) {
+        Ok(settings) => (Some(settings), report),
+        Err(e) => {
+            report.errors.push(e.to_string());
+            (None, report)
         }
     }
-    UprootedSettings::default()
 }
 
-#[allow(dead_code)]
+/// Load the settings file, migrated to the current schema; defaults when there is none or
+/// it fails to parse or validate. Never touches the file.
+pub fn load_settings() -> UprootedSettings {
+    load_settings_from(&settings_path())
+}
+
+fn load_settings_from(path: &Path) -> UprootedSettings {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| check_settings(&content).0)
+        .unwrap_or_default()
+}
+
+/// Move an unusable settings file aside before it gets overwritten, so what was in it isn't
+/// lost. Returns its new path, if there was one to move.
+pub fn quarantine_invalid_settings() -> Option<PathBuf> {
+    quarantine_invalid(&settings_path())
+}
+
+fn quarantine_invalid(path: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(path).ok()?;
+    if check_settings(&content).0.is_some() {
+        return None;
+    }
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let quarantined = path.with_file_name(format!("{}{}", QUARANTINE_PREFIX, secs));
+    fs::rename(path, &quarantined).ok()?;
+    Some(quarantined)
+}
+
+/// Settings files moved aside by [`quarantine_invalid_settings`], newest first.
+pub fn quarantined_files() -> Vec<PathBuf> {
+    quarantined_files_in(&get_profile_dir())
+}
+
+pub fn quarantined_files_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(QUARANTINE_PREFIX))
+        })
+        .collect();
+    files.sort();
+    files.reverse();
+    files
+}
+
 pub fn save_settings(settings: &UprootedSettings) -> Result<(), String> {
     let path = settings_path();
     if let Some(parent) = path.parent() {
@@ -51,5 +322,120 @@ pub fn save_settings(settings: &UprootedSettings) -> Result<(), String> {
     }
     let json =
         serde_json::to_string_pretty(settings).map_err(|e| format!("Failed to serialize: {}", e))?;
-    fs::write(&path, json).map_err(|e| format!("Failed to write settings: {}", e))
+    write_atomic(&path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> UprootedSettings {
+        serde_json::from_str(
+            r#"{
+                "enabled": true,
+                "plugins": {
+                    "themes": {"enabled": true, "config": {}},
+                    "silent-typing": {"enabled": true, "config": {}}
+                },
+                "customCss": "",
+                "injectionProfiles": {
+                    "theme-only": {"preload": false},
+                    "voice": {"plugins": ["themes"]}
+                },
+                "injectionTargets": [
+                    {"target": "RootApps/*/index.html", "profile": "theme-only"},
+                    {"target": "WebRtcBundle/*", "profile": "voice"},
+                    {"target": "Broken/*", "profile": "missing"}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn first_matching_target_picks_the_profile() {
+        let settings = settings();
+        let (name, profile) = settings.injection_profile(Path::new("RootApps/chess/index.html")).unwrap();
+        assert_eq!(name, "theme-only");
+        assert!(!profile.preload && profile.theme);
+
+        let (name, profile) = settings.injection_profile(Path::new("Other/index.html")).unwrap();
+        assert_eq!((name.as_str(), profile), (FULL_PROFILE, InjectionProfile::default()));
+
+        let err = settings.injection_profile(Path::new("Broken/index.html")).unwrap_err();
+        assert!(err.contains("unknown injection profile"), "{err}");
+    }
+
+    #[test]
+    fn profile_settings_disable_other_plugins_and_drop_installer_fields() {
+        let settings = settings();
+        let (_, profile) = settings.injection_profile(Path::new("WebRtcBundle/index.html")).unwrap();
+        let injected = settings.for_profile(&profile);
+        assert!(injected.plugins["themes"].enabled);
+        assert!(!injected.plugins["silent-typing"].enabled);
+
+        let json = serde_json::to_value(&injected).unwrap();
+        assert!(json.get("injectionProfiles").is_none());
+        assert!(json.get("injectionTargets").is_none());
+    }
+
+    #[test]
+    fn dotted_paths_get_set_and_unset() {
+        let mut settings = settings();
+        assert_eq!(settings.get("plugins.themes.enabled").unwrap(), Some(Value::Bool(true)));
+        assert_eq!(settings.get("plugins.nope.enabled").unwrap(), None);
+        assert!(settings.get("plugins..enabled").is_err());
+
+        settings.set("plugins.themes.config.theme", Value::from("crimson")).unwrap();
+        assert_eq!(settings.plugins["themes"].config["theme"], "crimson");
+        settings.set("plugins.link-embeds.config.youtube", Value::Bool(false)).unwrap();
+        assert!(!settings.plugins["link-embeds"].enabled);
+
+        let err = settings.set("plugins.themes.enabled", Value::from("yes")).unwrap_err();
+        assert!(err.contains("Invalid value for plugins.themes.enabled"), "{err}");
+        assert!(settings.set("enabled.nested", Value::Bool(true)).unwrap_err().contains("not an object"));
+        assert!(settings.plugins["themes"].enabled);
+
+        assert!(settings.unset("plugins.themes.config.theme").unwrap());
+        assert!(!settings.unset("plugins.themes.config.theme").unwrap());
+        assert!(settings.unset("customCss").unwrap_err().contains("missing field"));
+    }
+
+    #[test]
+    fn check_settings_migrates_or_rejects() {
+        let (settings, report) = check_settings(r#"{"plugins": {"themes": {"enabled": true}}}"#);
+        let settings = settings.unwrap();
+        assert_eq!((settings.version, report.version), (SETTINGS_VERSION, Some(0)));
+        assert!(settings.enabled && settings.plugins["themes"].config.is_empty());
+
+        let (settings, report) = check_settings("{\n  \"enabled\": tru\n}");
+        assert!(settings.is_none());
+        assert_eq!(report.errors, ["invalid JSON: expected ident at line 3 column 0"]);
+
+        let (settings, report) = check_settings(r#"{"plugins": {"themes": {"enabled": "on"}}}"#);
+        assert!(settings.is_none());
+        assert_eq!(report.errors, ["plugins.themes.enabled: expected true or false, found a string"]);
+    }
+
+    #[test]
+    fn invalid_files_are_only_moved_aside_when_asked() {
+        let dir = std::env::temp_dir().join(format!("uprooted-settings-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("uprooted-settings.json");
+
+        fs::write(&path, r#"{"enabled": false}"#).unwrap();
+        assert!(!load_settings_from(&path).enabled);
+        assert_eq!(quarantine_invalid(&path), None);
+
+        fs::write(&path, "{\"enabled\": fals").unwrap();
+        assert!(load_settings_from(&path).enabled, "defaults");
+        assert!(path.exists() && quarantined_files_in(&dir).is_empty());
+
+        let moved = quarantine_invalid(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "{\"enabled\": fals");
+        assert_eq!(quarantined_files_in(&dir), [moved]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
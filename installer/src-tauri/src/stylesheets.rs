@@ -0,0 +1,234 @@
+//! User stylesheets (`uprooted css add / remove / list`).
+//!
+//! `.css` files copied into `stylesheets/` in the uprooted dir. Depending on the
+//! `stylesheetInjection` setting they are compiled into a marked region at the end of
+//! `customCss`, or injected into each themed target as their own `<link>` (inline mode:
+//! `<style>`) after the theme. A file whose braces don't balance is never injected.
+
+use crate::fsutil::write_atomic;
+use crate::hook::get_uprooted_dir;
+use crate::settings::{StylesheetInjection, UprootedSettings};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Opens the compiled region of `customCss`; everything up to [`REGION_END`] is rewritten.
+const REGION_START: &str = "/* uprooted:stylesheets:start";
+const REGION_END: &str = "/* uprooted:stylesheets:end */";
+
+pub struct Stylesheet {
+    /// File name, e.g. `compact.css`.
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Stylesheet {
+    pub fn read(&self) -> Result<String, String> {
+        fs::read_to_string(&self.path).map_err(|e| format!("Failed to read {}: {}", self.path.display(), e))
+    }
+}
+
+pub fn stylesheet_dir() -> PathBuf {
+    get_uprooted_dir().join("stylesheets")
+}
+
+/// Managed stylesheets, by name.
+pub fn list() -> Vec<Stylesheet> {
+    let Ok(entries) = fs::read_dir(stylesheet_dir()) else {
+        return vec![];
+    };
+    let mut sheets: Vec<Stylesheet> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("css")))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            Some(Stylesheet { name, path })
+        })
+        .collect();
+    sheets.sort_by(|a, b| a.name.cmp(&b.name));
+    sheets
+}
+
+/// Whether `name` can be a managed stylesheet's file name.
+pub fn valid_name(name: &str) -> bool {
+    name.len() > ".css".len()
+        && name.to_ascii_lowercase().ends_with(".css")
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\'])
+}
+
+/// Copy `source` into the stylesheet directory, replacing one of the same name. Returns
+/// the name and whether it replaced an existing stylesheet.
+pub fn add(source: &Path) -> Result<(String, bool), String> {
+    let name = source.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+    if !valid_name(&name) {
+        return Err(format!("{} is not a .css file", source.display()));
+    }
+    let css = fs::read_to_string(source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    check_braces(&css).map_err(|e| format!("{}: {}", name, e))?;
+    let dir = stylesheet_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(&name);
+    let replaced = path.exists();
+    write_atomic(&path, css)?;
+    Ok((name, replaced))
+}
+
+/// Delete a managed stylesheet; the `.css` may be left off `name`.
+pub fn remove(name: &str) -> Result<String, String> {
+    let sheet = list()
+        .into_iter()
+        .find(|s| s.name == name || s.name.strip_suffix(".css") == Some(name))
+        .ok_or_else(|| format!("No stylesheet {} (see `uprooted css list`)", name))?;
+    fs::remove_file(&sheet.path).map_err(|e| format!("Failed to delete {}: {}", sheet.path.display(), e))?;
+    Ok(sheet.name)
+}
+
+/// Stylesheets fit to inject with their contents; one warning per one that isn't.
+pub fn usable(warnings: &mut Vec<String>) -> Vec<(Stylesheet, String)> {
+    let mut sheets = Vec::new();
+    for sheet in list() {
+        match sheet.read().and_then(|css| check_braces(&css).map(|()| css)) {
+            Ok(css) => sheets.push((sheet, css)),
+            Err(e) => warnings.push(format!("Stylesheet {} not injected: {}", sheet.name, e)),
+        }
+    }
+    sheets
+}
+
+/// Stylesheets to inject as their own tags into targets that get the theme: the usable
+/// ones in `link` mode, none in `custom-css` mode (they are in `customCss` already).
+pub fn tags_for(settings: &UprootedSettings, warnings: &mut Vec<String>) -> Vec<(Stylesheet, String)> {
+    if settings.stylesheet_injection != StylesheetInjection::Link {
+        return vec![];
+    }
+    let mut found = Vec::new();
+    let sheets = usable(&mut found);
+    for w in found {
+        if !warnings.contains(&w) {
+            warnings.push(w);
+        }
+    }
+    sheets
+}
+
+/// Bring the compiled region of `settings.custom_css` up to date with the stylesheet
+/// directory (removing it in `link` mode).
+pub fn apply(settings: &mut UprootedSettings, warnings: &mut Vec<String>) {
+    let sheets = match settings.stylesheet_injection {
+        StylesheetInjection::CustomCss => usable(warnings),
+        StylesheetInjection::Link => vec![],
+    };
+    settings.custom_css = compile_into(&settings.custom_css, &sheets);
+}
+
+/// `custom_css` with its compiled region replaced by `sheets` (dropped when empty).
+/// Everything outside the region is the user's own and kept as is.
+fn compile_into(custom_css: &str, sheets: &[(Stylesheet, String)]) -> String {
+    let mut css = match custom_css.find(REGION_START) {
+        Some(start) => {
+            let end = custom_css[start..]
+                .find(REGION_END)
+                .map_or(custom_css.len(), |i| start + i + REGION_END.len());
+            let after = custom_css[end..].strip_prefix('\n').unwrap_or(&custom_css[end..]);
+            format!("{}{}", &custom_css[..start], after)
+        }
+        None => custom_css.to_string(),
+    };
+    if sheets.is_empty() {
+        return css;
+    }
+    if !css.is_empty() && !css.ends_with('\n') {
+        css.push('\n');
+    }
+    css.push_str(REGION_START);
+    css.push_str(" (managed by `uprooted css`, edits here are overwritten) */\n");
+    for (sheet, content) in sheets {
+        css.push_str(&format!("/* {} */\n{}\n", sheet.name, content.trim_end()));
+    }
+    css.push_str(REGION_END);
+    css.push('\n');
+    css
+}
+
+/// Check that every `{` is closed, skipping comments and strings. The error names the
+/// line at fault.
+pub fn check_braces(css: &str) -> Result<(), String> {
+    let mut open: Vec<usize> = Vec::new();
+    let mut line = 1;
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '\\' => line += usize::from(chars.next() == Some('\n')),
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let start = line;
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        None => return Err(format!("line {}: comment is never closed", start)),
+                        Some('/') if prev == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            prev = c;
+                        }
+                    }
+                }
+            }
+            '"' | '\'' => loop {
+                match chars.next() {
+                    None | Some('\n') => return Err(format!("line {}: string is never closed", line)),
+                    Some('\\') => line += usize::from(chars.next() == Some('\n')),
+                    Some(q) if q == c => break,
+                    Some(_) => {}
+                }
+            },
+            '{' => open.push(line),
+            '}' => {
+                open.pop().ok_or_else(|| format!("line {}: `}}` without a matching `{{`", line))?;
+            }
+            _ => {}
+        }
+    }
+    match open.pop() {
+        Some(start) => Err(format!("line {}: `{{` is never closed", start)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braces_must_balance_outside_comments_and_strings() {
+        assert_eq!(check_braces("a { b: c; }\n@media x { a { } }\n"), Ok(()));
+        assert_eq!(check_braces("a::after { content: \"}\"; } /* { */\n"), Ok(()));
+        assert_eq!(check_braces("a {\n  b: c;\n\nd { }\n").unwrap_err(), "line 1: `{` is never closed");
+        assert_eq!(check_braces("a { }\n}\n").unwrap_err(), "line 2: `}` without a matching `{`");
+        assert_eq!(check_braces("a { }\n/* {\n").unwrap_err(), "line 2: comment is never closed");
+        assert_eq!(check_braces("a { content: 'x }\n").unwrap_err(), "line 1: string is never closed");
+    }
+
+    #[test]
+    fn compiled_region_is_replaced_and_removed() {
+        let sheet = |name: &str, css: &str| {
+            (Stylesheet { name: name.to_string(), path: PathBuf::from(name) }, css.to_string())
+        };
+        let mine = "body { color: red; }";
+        let compiled = compile_into(mine, &[sheet("a.css", "a { }\n"), sheet("b.css", "b { }")]);
+        assert_eq!(
+            compiled,
+            "body { color: red; }\n/* uprooted:stylesheets:start (managed by `uprooted css`, edits here are overwritten) */\n/* a.css */\na { }\n/* b.css */\nb { }\n/* uprooted:stylesheets:end */\n"
+        );
+
+        let recompiled = compile_into(&format!("{}/* after */", compiled), &[sheet("b.css", "b { }")]);
+        assert!(recompiled.starts_with("body { color: red; }\n/* after */\n/* uprooted:stylesheets:start"));
+        assert!(!recompiled.contains("a.css"));
+        assert_eq!(compile_into(&compiled, &[]), "body { color: red; }\n");
+        assert_eq!(compile_into("", &[]), "");
+    }
+}
@@ -0,0 +1,210 @@
+//! Which HTML files in Root's profile get patched.
+//!
+//! Targets are globs relative to the profile directory. The shipped rule file
+//! (`targets.json`) covers the surfaces we know about; `uprooted-targets.json` in the
+//! profile directory can replace its `rules` and add `include` / `exclude` globs, so a
+//! new Root surface can be patched without waiting for an installer release.
+
+use crate::detection::get_profile_dir;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_RULES: &str = include_str!("targets.json");
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    rules: Option<Vec<String>>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+pub struct TargetRules {
+    /// Target globs, from the user file if it sets `rules`, else the shipped ones.
+    pub rules: Vec<String>,
+    /// Extra target globs.
+    pub include: Vec<String>,
+    /// Globs of files never patched, even when a rule matches them.
+    pub exclude: Vec<String>,
+}
+
+impl TargetRules {
+    /// The rules shipped with the installer.
+    pub fn defaults() -> Self {
+        let file: RuleFile = serde_json::from_str(DEFAULT_RULES).expect("shipped targets.json is valid");
+        TargetRules {
+            rules: file.rules.unwrap_or_default(),
+            include: file.include,
+            exclude: file.exclude,
+        }
+    }
+
+    /// Apply a user rule file on top of these rules.
+    fn merge(mut self, file: RuleFile) -> Self {
+        if let Some(rules) = file.rules {
+            self.rules = rules;
+        }
+        self.include.extend(file.include);
+        self.exclude.extend(file.exclude);
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for pattern in self.rules.iter().chain(&self.include).chain(&self.exclude) {
+            glob::Pattern::new(pattern)
+                .map_err(|e| format!("Invalid target glob {:?}: {}", pattern, e))?;
+            if Path::new(pattern).is_absolute() || pattern.split(['/', '\\']).any(|c| c == "..") {
+                return Err(format!("Target glob {:?} must stay inside the profile directory", pattern));
+            }
+        }
+        Ok(())
+    }
+
+    /// Files under `profile` matched by a rule or include glob and no exclude glob, sorted.
+    pub fn find_targets(&self, profile: &Path) -> Vec<PathBuf> {
+        let base = glob::Pattern::escape(&profile.to_string_lossy());
+        let mut targets: Vec<PathBuf> = self
+            .rules
+            .iter()
+            .chain(&self.include)
+            .filter_map(|rule| glob::glob(&format!("{}/{}", base, rule)).ok())
+            .flat_map(|paths| paths.flatten())
+            .filter(|path| path.is_file() && !self.is_excluded(profile, path))
+            .collect();
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    /// HTML files under `profile` that no rule targets and no exclude glob covers.
+    pub fn find_unmatched(&self, profile: &Path) -> Vec<PathBuf> {
+        let targets = self.find_targets(profile);
+        find_html_files(profile)
+            .into_iter()
+            .filter(|path| !targets.contains(path) && !self.is_excluded(profile, path))
+            .collect()
+    }
+
+    fn is_excluded(&self, profile: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(profile) else {
+            return false;
+        };
+        self.exclude
+            .iter()
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .any(|pattern| pattern.matches_path(relative))
+    }
+}
+
+/// Every HTML file under `profile`, sorted.
+pub fn find_html_files(profile: &Path) -> Vec<PathBuf> {
+    let base = glob::Pattern::escape(&profile.to_string_lossy());
+    let Ok(paths) = glob::glob(&format!("{}/**/*.html", base)) else {
+        return vec![];
+    };
+    let mut files: Vec<PathBuf> = paths.flatten().filter(|path| path.is_file()).collect();
+    files.sort();
+    files
+}
+
+/// Per-user rule file, next to `uprooted-settings.json`.
+pub fn user_rules_path() -> PathBuf {
+    get_profile_dir().join("uprooted-targets.json")
+}
+
+/// Shipped rules with the user's rule file applied, if there is one.
+pub fn load_rules() -> Result<TargetRules, String> {
+    let rules = TargetRules::defaults();
+    let path = user_rules_path();
+    let rules = match fs::read_to_string(&path) {
+        Ok(content) => {
+            let file: RuleFile = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+            rules.merge(file)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => rules,
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    rules.validate()?;
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("uprooted-targets-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "<html></html>").unwrap();
+        }
+        dir
+    }
+
+    fn relative(profile: &Path, paths: Vec<PathBuf>) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.strip_prefix(profile).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    const FILES: &[&str] = &[
+        "WebRtcBundle/index.html",
+        "RootApps/chess/index.html",
+        "RootApps/notes/index.html",
+        "RootApps/notes/about.html",
+        "VoiceOverlay/index.html",
+    ];
+
+    #[test]
+    fn defaults_match_known_surfaces_and_report_new_ones() {
+        let dir = profile("defaults", FILES);
+        let rules = TargetRules::defaults();
+        assert_eq!(
+            relative(&dir, rules.find_targets(&dir)),
+            ["RootApps/chess/index.html", "RootApps/notes/index.html", "WebRtcBundle/index.html"]
+        );
+        assert_eq!(
+            relative(&dir, rules.find_unmatched(&dir)),
+            ["RootApps/notes/about.html", "VoiceOverlay/index.html"]
+        );
+        assert_eq!(find_html_files(&dir).len(), FILES.len());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn user_file_includes_and_excludes() {
+        let dir = profile("user", FILES);
+        let user: RuleFile = serde_json::from_str(
+            r#"{"include": ["VoiceOverlay/index.html"], "exclude": ["RootApps/chess/*", "RootApps/*/about.html"]}"#,
+        )
+        .unwrap();
+        let rules = TargetRules::defaults().merge(user);
+        assert!(rules.validate().is_ok());
+        assert_eq!(
+            relative(&dir, rules.find_targets(&dir)),
+            ["RootApps/notes/index.html", "VoiceOverlay/index.html", "WebRtcBundle/index.html"]
+        );
+        assert!(rules.find_unmatched(&dir).is_empty());
+
+        let user: RuleFile = serde_json::from_str(r#"{"rules": ["WebRtcBundle/*.html"]}"#).unwrap();
+        let rules = TargetRules::defaults().merge(user);
+        assert_eq!(relative(&dir, rules.find_targets(&dir)), ["WebRtcBundle/index.html"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_bad_rule_files() {
+        assert!(serde_json::from_str::<RuleFile>(r#"{"exclued": []}"#).is_err());
+        for bad in [r#"{"include": ["../other/index.html"]}"#, r#"{"include": ["[unclosed"]}"#] {
+            let user: RuleFile = serde_json::from_str(bad).unwrap();
+            assert!(TargetRules::defaults().merge(user).validate().is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Themes for the `themes` plugin (`uprooted theme list / set / preview / import`).
+//!
+//! Built-in themes are the plugin's own `src/plugins/themes/themes.json`, embedded at
+//! build time. Imported themes are kept as `themes/<name>.json` in the uprooted dir; the
+//! plugin only knows the built-in ones, so selecting an imported theme also writes its
+//! variables to `plugins.themes.config.variables`, which the plugin applies instead.
+
+use crate::embedded;
+use crate::fsutil::write_atomic;
+use crate::hook::get_uprooted_dir;
+use crate::settings::UprootedSettings;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Built-in theme whose variables the plugin derives from `customAccent` and
+/// `customBackground`.
+pub const CUSTOM_THEME: &str = "custom";
+/// Prefix of every variable a theme may set; Root's colours are `--rootsdk-*` properties.
+const VARIABLE_PREFIX: &str = "--rootsdk-";
+/// Colours `preview_colors` may give.
+const PREVIEW_KEYS: &[&str] = &["background", "text", "accent", "border"];
+
+/// An entry of `themes.json`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Theme {
+    pub name: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub preview_colors: BTreeMap<String, String>,
+}
+
+pub fn builtin() -> Vec<Theme> {
+    serde_json::from_str(embedded::THEMES_JSON).unwrap_or_default()
+}
+
+pub fn imported_dir() -> PathBuf {
+    get_uprooted_dir().join("themes")
+}
+
+/// Imported themes by name, skipping files that no longer parse.
+pub fn imported() -> Vec<Theme> {
+    let Ok(entries) = fs::read_dir(imported_dir()) else {
+        return vec![];
+    };
+    let mut themes: Vec<Theme> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| serde_json::from_str(&fs::read_to_string(entry.path()).ok()?).ok())
+        .collect();
+    themes.sort_by(|a, b| a.name.cmp(&b.name));
+    themes
+}
+
+/// A theme by name, and whether it is built in.
+pub fn find(name: &str) -> Option<(Theme, bool)> {
+    builtin()
+        .into_iter()
+        .map(|theme| (theme, true))
+        .chain(imported().into_iter().map(|theme| (theme, false)))
+        .find(|(theme, _)| theme.name == name)
+}
+
+/// Problems with a theme definition: errors (it can't be used) and warnings.
+pub fn validate(theme: &Theme) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    if theme.name.is_empty()
+        || !theme.name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        errors.push(format!("name {:?}: use lowercase letters, digits and '-'", theme.name));
+    }
+    if theme.display_name.trim().is_empty() {
+        errors.push("display_name: missing".to_string());
+    }
+
+    let known: Vec<String> = builtin().into_iter().flat_map(|t| t.variables.into_keys()).collect();
+    for (name, value) in &theme.variables {
+        let suffix = name.strip_prefix(VARIABLE_PREFIX).unwrap_or_default();
+        if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            errors.push(format!("variables.{}: not a {}* variable name", name, VARIABLE_PREFIX));
+        } else if !known.contains(name) {
+            warnings.push(format!("variables.{}: not used by any built-in theme, check the spelling", name));
+        }
+        // A value ends up inside a style declaration; keep it to a single value
+        if value.trim().is_empty() || value.contains([';', '{', '}', '<', '>']) {
+            errors.push(format!("variables.{}: invalid value {:?}", name, value));
+        }
+    }
+
+    for (key, colour) in &theme.preview_colors {
+        if !PREVIEW_KEYS.contains(&key.as_str()) {
+            warnings.push(format!("preview_colors.{}: unknown key, ignored", key));
+        } else if parse_hex(colour).is_none() {
+            errors.push(format!("preview_colors.{}: expected #RRGGBB, found {:?}", key, colour));
+        }
+    }
+    (errors, warnings)
+}
+
+/// Validate the theme in `path` and store it with the imported themes, replacing an
+/// imported theme of the same name. Returns it with any warnings.
+pub fn import(path: &Path) -> Result<(Theme, Vec<String>), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let theme: Theme = serde_json::from_str(&content)
+        .map_err(|e| format!("{} is not a theme definition: {}", path.display(), e))?;
+    let (errors, warnings) = validate(&theme);
+    if !errors.is_empty() {
+        return Err(format!("{} is not a valid theme: {}", path.display(), errors.join("; ")));
+    }
+    if builtin().iter().any(|t| t.name == theme.name) {
+        return Err(format!("{} is a built-in theme; rename the imported one", theme.name));
+    }
+    let dir = imported_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let json = serde_json::to_string_pretty(&theme).map_err(|e| format!("Failed to serialize: {}", e))?;
+    write_atomic(&dir.join(format!("{}.json", theme.name)), json)?;
+    Ok((theme, warnings))
+}
+
+/// Select `theme` in `settings`: its name in `plugins.themes.config.theme`, plus the
+/// variables of an imported theme in `plugins.themes.config.variables`.
+pub fn select(settings: &mut UprootedSettings, theme: &Theme, builtin: bool) -> Result<(), String> {
+    settings.set("plugins.themes.config.theme", Value::from(theme.name.as_str()))?;
+    if builtin {
+        settings.unset("plugins.themes.config.variables")?;
+    } else {
+        let variables = serde_json::to_value(&theme.variables).map_err(|e| format!("Failed to serialize: {}", e))?;
+        settings.set("plugins.themes.config.variables", variables)?;
+    }
+    Ok(())
+}
+
+/// The selected theme's name, `default` when none is.
+pub fn selected(settings: &UprootedSettings) -> String {
+    settings
+        .plugins
+        .get("themes")
+        .and_then(|p| p.config.get("theme"))
+        .and_then(Value::as_str)
+        .unwrap_or("default")
+        .to_string()
+}
+
+/// `#RRGGBB` as red, green and blue.
+pub fn parse_hex(colour: &str) -> Option<(u8, u8, u8)> {
+    let hex = colour.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme(variables: &[(&str, &str)]) -> Theme {
+        Theme {
+            name: "ocean".to_string(),
+            display_name: "Ocean".to_string(),
+            description: String::new(),
+            author: String::new(),
+            variables: variables.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            preview_colors: [("accent".to_string(), "#1E90FF".to_string())].into(),
+        }
+    }
+
+    #[test]
+    fn builtin_themes_are_valid() {
+        let themes = builtin();
+        assert!(themes.iter().any(|t| t.name == "default") && themes.iter().any(|t| t.name == CUSTOM_THEME));
+        for theme in &themes {
+            assert_eq!(validate(theme), (vec![], vec![]), "{}", theme.name);
+        }
+    }
+
+    #[test]
+    fn variable_names_and_values_are_checked() {
+        assert_eq!(validate(&theme(&[("--rootsdk-brand-primary", "#1E90FF")])), (vec![], vec![]));
+
+        let (errors, warnings) = validate(&theme(&[
+            ("--color-accent", "red"),
+            ("--rootsdk-", "red"),
+            ("--rootsdk-link", "red; } body { display: none"),
+            ("--rootsdk-brand-primery", "#1E90FF"),
+        ]));
+        assert_eq!(
+            errors,
+            [
+                "variables.--color-accent: not a --rootsdk-* variable name",
+                "variables.--rootsdk-: not a --rootsdk-* variable name",
+                "variables.--rootsdk-link: invalid value \"red; } body { display: none\"",
+            ]
+        );
+        assert_eq!(warnings, ["variables.--rootsdk-brand-primery: not used by any built-in theme, check the spelling"]);
+
+        let mut settings = UprootedSettings::default();
+        select(&mut settings, &theme(&[("--rootsdk-link", "#fff")]), false).unwrap();
+        assert_eq!(selected(&settings), "ocean");
+        assert_eq!(settings.plugins["themes"].config["variables"]["--rootsdk-link"], "#fff");
+        select(&mut settings, &builtin()[0], true).unwrap();
+        assert!(!settings.plugins["themes"].config.contains_key("variables"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Settings sync (`uprooted watch`).
+//!
+//! Polls `uprooted-settings.json` and, once an edit has settled, carries it into the
+//! patched HTML with [`patcher::sync_settings`], which rewrites only the settings line of
+//! each block where nothing else changed. Every write goes through `write_atomic`, so Root
+//! never reads a half-written file. A settings file that doesn't validate is reported and
+//! skipped (it is likely mid-edit), never quarantined.
+
+use crate::hash;
+use crate::patcher::{self, SyncKind};
+use crate::settings::{check_settings, settings_path};
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the settings file is looked at.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How long the file must stay unchanged before it is synced; editors often save in
+/// several writes (truncate, write, rename).
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// What identifies a version of the settings file without reading it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = fs::metadata(path).ok()?;
+    Some(Stamp { modified: meta.modified().ok(), len: meta.len() })
+}
+
+/// Waits for a stamp to stop changing.
+struct Debouncer {
+    seen: Option<Stamp>,
+    changed_at: Option<Instant>,
+}
+
+impl Debouncer {
+    fn new(seen: Option<Stamp>) -> Self {
+        Debouncer { seen, changed_at: None }
+    }
+
+    /// Record the stamp polled at `now`; true once a change has been stable for [`DEBOUNCE`].
+    fn settled(&mut self, stamp: Option<Stamp>, now: Instant) -> bool {
+        if stamp != self.seen {
+            self.seen = stamp;
+            self.changed_at = Some(now);
+            return false;
+        }
+        match self.changed_at {
+            Some(at) if now.duration_since(at) >= DEBOUNCE => {
+                self.changed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// One sync of the settings file into the patched HTML.
+pub enum Event {
+    /// Targets rewritten, with how, and warnings.
+    Synced(Vec<(String, SyncKind)>, Vec<String>),
+    /// The settings file can't be used as it is; nothing was written.
+    Invalid(Vec<String>),
+    /// The settings file is gone; the injected settings are left as they are.
+    Missing,
+    /// Writing a target failed.
+    Failed(String),
+}
+
+/// Sync the settings file as it is now.
+pub fn sync_once() -> Event {
+    match fs::read_to_string(settings_path()) {
+        Ok(content) => sync_content(&content),
+        Err(_) => Event::Missing,
+    }
+}
+
+fn sync_content(content: &str) -> Event {
+    let (settings, report) = check_settings(content);
+    let Some(settings) = settings else {
+        return Event::Invalid(report.errors);
+    };
+    let mut warnings = Vec::new();
+    match patcher::sync_settings(&settings, &mut warnings) {
+        Ok(synced) => Event::Synced(synced, warnings),
+        Err(e) => Event::Failed(e),
+    }
+}
+
+/// Watch the settings file until the process is stopped, calling `on_event` after each
+/// settled change. Saves that leave the content as it was (a touch, an unchanged save)
+/// are not synced.
+pub fn run(mut on_event: impl FnMut(Event)) -> ! {
+    let path = settings_path();
+    let read_hash = || fs::read(&path).ok().map(|bytes| hash::sha256_hex(&bytes));
+    let mut debouncer = Debouncer::new(stamp(&path));
+    let mut synced_hash = read_hash();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if !debouncer.settled(stamp(&path), Instant::now()) {
+            continue;
+        }
+        let content = fs::read_to_string(&path).ok();
+        let content_hash = content.as_deref().map(|c| hash::sha256_hex(c.as_bytes()));
+        if content_hash == synced_hash {
+            continue;
+        }
+        synced_hash = content_hash;
+        on_event(match content {
+            Some(content) => sync_content(&content),
+            None => Event::Missing,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_settle_after_the_debounce() {
+        let stamp = |len| Some(Stamp { modified: None, len });
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut debouncer = Debouncer::new(stamp(1));
+
+        assert!(!debouncer.settled(stamp(1), at(1000)), "nothing changed");
+        // A save in three writes only counts from the last one
+        assert!(!debouncer.settled(stamp(0), at(1000)));
+        assert!(!debouncer.settled(stamp(5), at(1200)));
+        assert!(!debouncer.settled(stamp(9), at(1400)));
+        assert!(!debouncer.settled(stamp(9), at(1800)));
+        assert!(debouncer.settled(stamp(9), at(1900)));
+        assert!(!debouncer.settled(stamp(9), at(3000)), "synced once per change");
+
+        assert!(!debouncer.settled(None, at(3200)));
+        assert!(debouncer.settled(None, at(3700)));
+    }
+
+    #[test]
+    fn invalid_settings_are_not_synced() {
+        match sync_content("{\"enabled\": true,") {
+            Event::Invalid(errors) => assert!(errors[0].starts_with("invalid JSON"), "{errors:?}"),
+            _ => panic!("expected Invalid"),
+        }
+    }
+}
@@ -5,6 +5,7 @@
  * This plugin overrides those variables to apply custom themes.
  *
  * Theme definitions are loaded from themes.json (shared with installer backend).
+ * Themes imported through the installer carry their variables in the plugin config.
  * For the "custom" theme, CSS variables are generated at runtime from
  * user-chosen accent + background colors.
  */
@@ -119,9 +120,15 @@ export default {
       return;
     }
 
+    // Themes imported with `uprooted theme import` aren't in themes.json; the installer
+    // writes their variables into the config instead
     const theme = (themes as ThemeDef[]).find((t) => t.name === themeName);
-    if (theme && Object.keys(theme.variables).length > 0) {
-      setCssVariables(theme.variables);
+    const variables = theme?.variables ?? (settings?.variables as Record<string, string> | undefined) ?? {};
+    for (const name of Object.keys(variables)) {
+      allVarNames.add(name);
+    }
+    if (Object.keys(variables).length > 0) {
+      setCssVariables(variables);
     }
   },
 
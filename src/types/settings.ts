@@ -4,6 +4,8 @@ export interface PluginSettings {
 }
 
 export interface UprootedSettings {
+  /** Schema version, maintained by the installer's migrations. */
+  version?: number;
   /** Whether Uprooted is globally enabled. */
   enabled: boolean;
   /** Per-plugin settings keyed by plugin name. */